@@ -1,17 +1,29 @@
 use axum::{
     routing::get,
-    extract::Path,
+    extract::{Path, State},
     handler::HandlerWithoutStateExt,
-    http::{StatusCode, header},
-    response::IntoResponse,
+    http::{StatusCode, Uri, header},
+    response::{IntoResponse, Response},
     Router,
 };
-use std::{fs, net::SocketAddr};
+use std::{
+    collections::HashMap,
+    fs,
+    sync::{Arc, Mutex},
+};
 
 use tower_http::{
     services::{ServeDir, ServeFile},
 };
 
+// The size Open Graph previews are shown at
+const PREVIEW_WIDTH: u32 = 1200;
+const PREVIEW_HEIGHT: u32 = 630;
+
+// Rendered previews by path. Scenes are built into the binary, so each one only ever
+// needs drawing once.
+type PreviewCache = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
 #[tokio::main]
 async fn main() {
     async fn handle_404() -> (StatusCode, &'static str) {
@@ -23,14 +35,19 @@ async fn main() {
         .route("/", get(serve_blog_index))
         .route("/:slug", get(serve_blog));
 
+    let og_service = Router::new()
+        .route("/:name", get(serve_preview))
+        .with_state(PreviewCache::default());
+
     let app = Router::new()
-        .nest_service("/", ServeFile::new("./static/index.html"))
         .nest_service("/blog", blog_service)
+        .nest_service("/og", og_service)
         .nest_service("/art.pub", ServeFile::new("./static/art.pub"))
         .nest_service(
             "/static",
             ServeDir::new("./static").not_found_service(not_found_service),
-        );
+        )
+        .fallback(serve_page);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:80").await.unwrap();
     axum::serve(listener, app).await.unwrap();
@@ -61,7 +78,7 @@ async fn serve_blog_index() -> impl IntoResponse {
     let file_path = "./static/blog/index.html";
 
     // Attempt to read the file
-    match fs::read_to_string(&file_path) {
+    match fs::read_to_string(file_path) {
         Ok(contents) => (
             StatusCode::OK,
             [(header::CONTENT_TYPE, "text/html")],
@@ -75,3 +92,102 @@ async fn serve_blog_index() -> impl IntoResponse {
             .into_response(),
     }
 }
+
+// The page, with its link preview pointing at the scene for the path asked for.
+// Paths without a scene keep the home page's.
+async fn serve_page(uri: Uri) -> Response {
+    let contents = match fs::read_to_string("./static/index.html") {
+        Ok(contents) => contents,
+        Err(_) => return (StatusCode::NOT_FOUND, "404: File not found").into_response(),
+    };
+
+    let contents = match cubes::preview_name(uri.path()) {
+        Some(name) => {
+            let image = format!("https://art.cx/og/{}.png", name);
+            let contents = set_meta(&contents, "og:image", &image);
+            set_meta(&contents, "og:title", &page_title(name))
+        }
+        None => contents,
+    };
+
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/html")],
+        contents,
+    )
+        .into_response()
+}
+
+// "Artur Sapek" for the home page, "Projects - Artur Sapek" for /projects
+fn page_title(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) if name != "index" => {
+            format!("{}{} - Artur Sapek", first.to_uppercase(), chars.as_str())
+        }
+        _ => "Artur Sapek".to_string(),
+    }
+}
+
+// Swaps the content of a <meta property="..."> tag, leaving the page alone if it
+// doesn't have one
+fn set_meta(html: &str, property: &str, content: &str) -> String {
+    let tag = format!("property=\"{}\" content=\"", property);
+    let Some(start) = html.find(&tag).map(|index| index + tag.len()) else {
+        return html.to_string();
+    };
+    let Some(end) = html[start..].find('"').map(|index| start + index) else {
+        return html.to_string();
+    };
+
+    format!("{}{}{}", &html[..start], content, &html[end..])
+}
+
+// A picture of the scene at a path, the way it starts off on the page, for link
+// previews: /og/index.png for the home page, /og/projects.png for /projects
+async fn serve_preview(
+    State(cache): State<PreviewCache>,
+    Path(name): Path<String>,
+) -> Response {
+    let path = match name.strip_suffix(".png") {
+        Some("index") => "/".to_string(),
+        Some(name) => format!("/{}", name),
+        None => return (StatusCode::NOT_FOUND, "404: File not found").into_response(),
+    };
+
+    let cached = cache.lock().unwrap().get(&path).cloned();
+    let png = match cached {
+        Some(png) => png,
+        None => {
+            let render_path = path.clone();
+            let rendered = tokio::task::spawn_blocking(move || {
+                cubes::route_to_png(&render_path, PREVIEW_WIDTH, PREVIEW_HEIGHT)
+            })
+            .await;
+
+            match rendered {
+                Ok(Ok(png)) => {
+                    cache.lock().unwrap().insert(path, png.clone());
+                    png
+                }
+                Ok(Err(cubes::SceneError::NotFound { .. })) => {
+                    return (StatusCode::NOT_FOUND, "404: File not found").into_response()
+                }
+                Ok(Err(error)) => {
+                    return (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response()
+                }
+                Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            }
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "image/png"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+        ],
+        png,
+    )
+        .into_response()
+}
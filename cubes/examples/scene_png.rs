@@ -0,0 +1,30 @@
+// Draws one of the built-in scenes as a PNG, the way the page starts off at that path.
+// This is what the README's preview is made with:
+//
+//     cargo run --release --features raster --example scene_png -- / 1428 896 > ../preview.png
+use std::io::Write;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(path) = args.first() else {
+        eprintln!("usage: scene_png <path> [width] [height]");
+        return ExitCode::FAILURE;
+    };
+    let size = |i: usize, default: u32| {
+        args.get(i)
+            .and_then(|arg| arg.parse().ok())
+            .unwrap_or(default)
+    };
+
+    match artcx::route_to_png(path, size(1, 1200), size(2, 630)) {
+        Ok(png) => {
+            let _ = std::io::stdout().write_all(&png);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
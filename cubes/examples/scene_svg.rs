@@ -0,0 +1,36 @@
+// Draws a scene file as an SVG, the way the page starts off:
+//
+//     cargo run --example scene_svg -- scenes/home.json 1200 800 > home.svg
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(file) = args.first() else {
+        eprintln!("usage: scene_svg <scene.json> [width] [height]");
+        return ExitCode::FAILURE;
+    };
+    let size = |i: usize, default: f64| {
+        args.get(i)
+            .and_then(|arg| arg.parse().ok())
+            .unwrap_or(default)
+    };
+
+    let source = match std::fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("{}: {}", file, error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match artcx::scene_to_svg(file, &source, size(1, 1200.0), size(2, 800.0)) {
+        Ok(svg) => {
+            print!("{}", svg);
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
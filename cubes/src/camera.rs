@@ -0,0 +1,154 @@
+use geo::Coord;
+use serde::Deserialize;
+
+// How much one press of + or - zooms by
+pub const ZOOM_STEP: f64 = 1.25;
+
+// How far the arrow keys pan (CSS pixels)
+pub const PAN_STEP: f64 = 80.0;
+
+// How much a wheel or trackpad pinch zooms per pixel it scrolls
+pub const WHEEL_ZOOM_SPEED: f64 = 0.01;
+
+// How quickly the view turns to a new angle: this is how long (ms) it takes to get
+// about two thirds of the way there
+const ROTATION_TIME: f64 = 120.0;
+
+// Close enough to the angle it's turning to that it may as well be there (quarter turns)
+const ROTATION_SNAP: f64 = 0.001;
+
+// Where the view is looking and how closely. This is applied on top of where the
+// scene sits by default, so an untouched camera shows it just like it always has.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    // How far the view has been dragged from where it started (device pixels)
+    pub pan: Coord,
+    pub zoom: f64,
+    pub limits: CameraLimits,
+
+    // How far the floor is turned around the pivot (quarter turns, clockwise on screen).
+    // It eases towards the target, which is always a whole number of quarter turns.
+    pub rotation: f64,
+    pub target_rotation: f64,
+    // What the floor turns around (iso units), usually the middle of the scene
+    pub pivot: Coord,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            pan: Coord { x: 0.0, y: 0.0 },
+            zoom: 1.0,
+            limits: CameraLimits::default(),
+            rotation: 0.0,
+            target_rotation: 0.0,
+            pivot: Coord { x: 0.0, y: 0.0 },
+        }
+    }
+}
+
+impl Camera {
+    // The turn in radians
+    pub fn angle(&self) -> f64 {
+        self.rotation * std::f64::consts::FRAC_PI_2
+    }
+
+    // cos and sin of the turn. Whole quarter turns come out exact, so a turned view lines
+    // up with the grid just as well as an unturned one.
+    pub fn cos_sin(&self) -> (f64, f64) {
+        if self.rotation.fract() == 0.0 {
+            match (self.rotation as i64).rem_euclid(4) {
+                0 => (1.0, 0.0),
+                1 => (0.0, 1.0),
+                2 => (-1.0, 0.0),
+                _ => (0.0, -1.0),
+            }
+        } else {
+            let angle = self.angle();
+            (angle.cos(), angle.sin())
+        }
+    }
+
+    // Eases the turn along for `dt` ms. Returns whether it's still turning.
+    pub fn step_rotation(&mut self, dt: f64) -> bool {
+        if self.rotation == self.target_rotation {
+            return false;
+        }
+
+        let remaining = self.target_rotation - self.rotation;
+        self.rotation += remaining * (1.0 - (-dt / ROTATION_TIME).exp());
+
+        if (self.target_rotation - self.rotation).abs() < ROTATION_SNAP {
+            self.rotation = self.target_rotation;
+        }
+
+        true
+    }
+}
+
+// How far a scene lets visitors zoom and wander off
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraLimits {
+    pub min_zoom: f64,
+    pub max_zoom: f64,
+    // The part of the floor (iso units) the middle of the screen has to stay over
+    pub bounds: Option<Bounds>,
+}
+
+impl Default for CameraLimits {
+    fn default() -> Self {
+        Self {
+            min_zoom: 0.25,
+            max_zoom: 4.0,
+            bounds: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bounds {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Bounds {
+    pub fn clamp(&self, point: Coord) -> Coord {
+        Coord {
+            x: point.x.clamp(self.min_x, self.max_x),
+            y: point.y.clamp(self.min_y, self.max_y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation() {
+        let mut camera = Camera::default();
+        assert_eq!(camera.cos_sin(), (1.0, 0.0));
+        assert!(!camera.step_rotation(16.0));
+
+        camera.target_rotation = -1.0;
+        let mut last = camera.rotation;
+        let mut steps = 0;
+        while camera.step_rotation(16.0) {
+            // Always heading the right way, without overshooting
+            assert!(camera.rotation < last && camera.rotation >= -1.0);
+            last = camera.rotation;
+            steps += 1;
+        }
+        assert!(steps > 5 && steps < 100);
+        assert_eq!(camera.rotation, -1.0);
+        assert_eq!(camera.cos_sin(), (0.0, -1.0));
+
+        camera.rotation = 0.5;
+        let (cos, sin) = camera.cos_sin();
+        assert!((cos - sin).abs() < 1e-12);
+    }
+}
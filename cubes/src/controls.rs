@@ -0,0 +1,105 @@
+use geo::Coord;
+
+use crate::grid::Grid;
+use crate::render::{FillRule, Renderer, TextAlign, TextBaseline};
+
+// Buttons drawn over the scene in the bottom right corner, for turning the view
+// without a keyboard
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    RotateLeft,
+    RotateRight,
+}
+
+// Sizes and spacing, in CSS pixels
+const RADIUS: f64 = 20.0;
+const MARGIN: f64 = 16.0;
+const GAP: f64 = 8.0;
+const FONT_SIZE: f64 = 24.0;
+
+impl Control {
+    pub const ALL: [Control; 2] = [Control::RotateLeft, Control::RotateRight];
+
+    // Which control a pointer position (in CSS pixels) is over, if any. These are drawn
+    // on top of everything, so they're checked before cubes and links.
+    pub fn hit_test(posn: &Coord, grid: &Grid) -> Option<Control> {
+        Control::ALL.into_iter().find(|control| {
+            let center = control.center(grid);
+            (posn.x - center.x).hypot(posn.y - center.y) <= RADIUS
+        })
+    }
+
+    // Clockwise on screen is positive
+    pub fn quarter_turns(self) -> i32 {
+        match self {
+            Control::RotateLeft => -1,
+            Control::RotateRight => 1,
+        }
+    }
+
+    pub fn draw(self, renderer: &dyn Renderer, grid: &Grid, is_active: bool) {
+        let dpr = grid.device_pixel_ratio;
+        let center = grid.to_device(&self.center(grid));
+
+        renderer.begin_path();
+        renderer.arc(center.x, center.y, RADIUS * dpr, 0.0, std::f64::consts::TAU);
+        renderer.set_fill_style("rgba(255, 255, 255, 0.9)");
+        renderer.fill(FillRule::NonZero);
+        renderer.set_line_width(2.0 * dpr);
+        renderer.set_stroke_style(if is_active {
+            "#0000ff"
+        } else {
+            "hsl(213deg, 20%, 55%)"
+        });
+        renderer.stroke();
+
+        let label = match self {
+            Control::RotateLeft => "↺",
+            Control::RotateRight => "↻",
+        };
+        renderer.set_font(&format!("{}px sans-serif", FONT_SIZE * dpr));
+        renderer.set_text_align(TextAlign::Center);
+        renderer.set_text_baseline(TextBaseline::Middle);
+        renderer.set_fill_style("#294252");
+        renderer.fill_text(label, center.x, center.y);
+    }
+
+    // The middle of the button (CSS pixels)
+    fn center(self, grid: &Grid) -> Coord {
+        let width = grid.width / grid.device_pixel_ratio;
+        let height = grid.height / grid.device_pixel_ratio;
+
+        let from_right = match self {
+            Control::RotateRight => 0.0,
+            Control::RotateLeft => 1.0,
+        };
+
+        Coord {
+            x: width - MARGIN - RADIUS - from_right * (RADIUS * 2.0 + GAP),
+            y: height - MARGIN - RADIUS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::coord;
+
+    #[test]
+    fn test_hit_test() {
+        let mut grid = Grid::default();
+        grid.update_dimensions(1000.0, 800.0, 2.0);
+
+        let right = coord! { x: 1000.0 - 36.0, y: 800.0 - 36.0 };
+        assert_eq!(Control::hit_test(&right, &grid), Some(Control::RotateRight));
+
+        let left = coord! { x: right.x - 48.0, y: right.y + 10.0 };
+        assert_eq!(Control::hit_test(&left, &grid), Some(Control::RotateLeft));
+
+        assert_eq!(
+            Control::hit_test(&coord! { x: 500.0, y: 400.0 }, &grid),
+            None
+        );
+    }
+}
@@ -1,12 +1,8 @@
 use crate::grid::Grid;
-use geo::algorithm::LineIntersection;
-use geo::{polygon, Contains, Coord, Line, Polygon, Rect};
-use std::cmp::Ordering;
-use wasm_bindgen::prelude::JsValue;
-
-const CUBE_COLOR_LEFT: &str = "hsl(213deg, 20%, 81%)";
-const CUBE_COLOR_RIGHT: &str = "hsl(213deg, 20%, 72%)";
-const CUBE_COLOR_TOP: &str = "hsl(213deg, 20%, 93%)";
+use crate::render::{FillRule, LineJoin, Renderer, TextAlign, TextBaseline};
+use crate::shadow::{self, Surface};
+use geo::{Coord, Rect};
+
 const CUBE_COLOR_STROKE: &str = "hsl(213deg, 20%, 55%)";
 
 #[derive(Debug, Clone, Copy)]
@@ -15,13 +11,51 @@ pub struct Cube {
 
     character: char,
     pub coord: Coord,
+    // Where the scene put it
+    pub home: Coord,
     pub size: f64,
     pub bounds: Rect, // cache
 
+    // Height of the cube's bottom face above the floor
+    pub z: f64,
+
+    // Iso units per ms, while sliding after being let go
+    pub velocity: Coord,
+    // Iso units per ms, negative while falling
+    pub velocity_z: f64,
+
     // Mouse interaction state
     pub is_active: bool,
 }
 
+// The faces of a cube we might see: the top, and the sides named by which edge of the
+// cube they're on. Unturned, MaxY is drawn on the left and MaxX on the right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Face {
+    Top,
+    MinX,
+    MaxX,
+    MinY,
+    MaxY,
+}
+
+// One of a cube's upright sides, as its two corners on the floor and which way it faces
+struct Side {
+    face: Face,
+    normal: Coord,
+    start: Coord,
+    end: Coord,
+}
+
+// A point on one of a cube's faces, in world coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub face: Face,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
 pub struct Corners {
     pub tl: Coord,
     pub tr: Coord,
@@ -35,7 +69,11 @@ impl Cube {
             id: 0,
             character,
             coord: Coord { x, y },
+            home: Coord { x, y },
             size,
+            z: 0.0,
+            velocity: Coord { x: 0.0, y: 0.0 },
+            velocity_z: 0.0,
             is_active: false,
             bounds: Rect::new(
                 Coord { x, y },
@@ -47,241 +85,227 @@ impl Cube {
         }
     }
 
-    #[allow(unused_must_use)]
-    pub fn draw(&self, context: &web_sys::CanvasRenderingContext2d, grid: &Grid) {
-        context.begin_path();
-
+    // Draws the cube, with the shadows any of `others` cast on it. Each face's shadows
+    // go on right after the face, so whatever's drawn in front later covers them too.
+    pub fn draw(&self, renderer: &dyn Renderer, grid: &Grid, others: &[Cube]) {
         let size = self.size;
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let (bottom, top) = (self.z, self.top());
+
+        let outline = |corners: [Coord; 4]| {
+            renderer.begin_path();
+            renderer.move_to(corners[0].x, corners[0].y);
+            for corner in &corners[1..] {
+                renderer.line_to(corner.x, corner.y);
+            }
+            renderer.close_path();
+        };
+        let stroke = || {
+            renderer.set_line_width(2.0);
+            renderer.set_line_join(LineJoin::Round);
+            renderer.set_stroke_style(CUBE_COLOR_STROKE);
+
+            if self.is_active {
+                renderer.set_line_width(4.0);
+                renderer.set_stroke_style("#0000ff");
+            }
 
-        context.set_line_width(2.0);
-        context.set_line_join("round");
-        context.set_stroke_style(&JsValue::from_str(CUBE_COLOR_STROKE));
+            renderer.stroke();
+        };
 
-        if self.is_active {
-            context.set_line_width(4.0);
-            context.set_stroke_style(&JsValue::from_str("#0000ff"));
-        }
+        // Every face is shaded by how squarely it faces the light, and darkened to just
+        // the ambient light wherever another cube blocks it
+        let shade = |normal: [f64; 3], center: [f64; 3]| {
+            grid.shading.face_color(normal, center, &grid.light_source)
+        };
+        let draw_shadows = |surface: &Surface| {
+            let shadows = shadow::shadows_on(surface, others, &grid.light_source);
+            if shadows.is_empty() {
+                return;
+            }
 
-        // Draw left face
-        {
-            let a = grid.project(self.coord.x + size, self.coord.y + size, 0.0);
-            let b = grid.project(self.coord.x + size, self.coord.y + size, size);
-            let c = grid.project(self.coord.x, self.coord.y + size, size);
-            let d = grid.project(self.coord.x, self.coord.y + size, 0.0);
-
-            context.begin_path();
-            context.move_to(a.x, a.y);
-            context.line_to(b.x, b.y);
-            context.line_to(c.x, c.y);
-            context.line_to(d.x, d.y);
-            context.line_to(a.x, a.y);
-            context.set_fill_style(&JsValue::from_str(CUBE_COLOR_LEFT));
-            context.fill();
-            context.stroke();
-        }
+            renderer.begin_path();
+            for polygon in &shadows {
+                for (i, point) in polygon.exterior().coords().enumerate() {
+                    let [x, y, z] = surface.world_point(*point);
+                    let point = grid.project(x, y, z);
+                    if i == 0 {
+                        renderer.move_to(point.x, point.y);
+                    } else {
+                        renderer.line_to(point.x, point.y);
+                    }
+                }
+                renderer.close_path();
+            }
+            renderer.set_fill_style(&grid.shading.shadow_color());
+            renderer.fill(FillRule::NonZero);
+        };
 
-        // Draw right face
-        {
-            let a = grid.project(self.coord.x + size, self.coord.y + size, 0.0);
-            let b = grid.project(self.coord.x + size, self.coord.y + size, size);
-            let c = grid.project(self.coord.x + size, self.coord.y, size);
-            let d = grid.project(self.coord.x + size, self.coord.y, 0.0);
-            context.begin_path();
-            context.move_to(a.x, a.y);
-            context.line_to(b.x, b.y);
-            context.line_to(c.x, c.y);
-            context.line_to(d.x, d.y);
-            context.line_to(a.x, a.y);
-            context.set_fill_style(&JsValue::from_str(CUBE_COLOR_RIGHT));
-            context.fill();
-            context.stroke();
+        // Draw whichever sides face the viewer. Unturned, those are the left and right.
+        for side in self.visible_sides(grid) {
+            let corners = [
+                grid.project(side.start.x, side.start.y, bottom),
+                grid.project(side.start.x, side.start.y, top),
+                grid.project(side.end.x, side.end.y, top),
+                grid.project(side.end.x, side.end.y, bottom),
+            ];
+
+            let normal = [side.normal.x, side.normal.y, 0.0];
+            let center = [
+                (side.start.x + side.end.x) / 2.0,
+                (side.start.y + side.end.y) / 2.0,
+                (bottom + top) / 2.0,
+            ];
+
+            outline(corners);
+            renderer.set_fill_style(&shade(normal, center));
+            renderer.fill(FillRule::NonZero);
+
+            // Points on a side are (y, z) or (x, z), depending on which way it faces
+            let surface = if side.normal.x != 0.0 {
+                Surface {
+                    axis: 0,
+                    at: side.start.x,
+                    facing: side.normal.x,
+                    rect: Rect::new(
+                        Coord {
+                            x: min.y,
+                            y: bottom,
+                        },
+                        Coord { x: max.y, y: top },
+                    ),
+                }
+            } else {
+                Surface {
+                    axis: 1,
+                    at: side.start.y,
+                    facing: side.normal.y,
+                    rect: Rect::new(
+                        Coord {
+                            x: min.x,
+                            y: bottom,
+                        },
+                        Coord { x: max.x, y: top },
+                    ),
+                }
+            };
+            draw_shadows(&surface);
+
+            outline(corners);
+            stroke();
         }
 
         // Draw top face
         {
-            let a = grid.project(self.coord.x, self.coord.y + size, size);
-            let b = grid.project(self.coord.x + size, self.coord.y + size, size);
-            let c = grid.project(self.coord.x + size, self.coord.y, size);
-            let d = grid.project(self.coord.x, self.coord.y, size);
-            context.begin_path();
-            context.move_to(a.x, a.y);
-            context.line_to(b.x, b.y);
-            context.line_to(c.x, c.y);
-            context.line_to(d.x, d.y);
-            context.line_to(a.x, a.y);
-            context.set_fill_style(&JsValue::from_str(CUBE_COLOR_TOP));
-            context.fill();
-            context.stroke();
+            let corners = [
+                grid.project(self.coord.x, self.coord.y + size, top),
+                grid.project(self.coord.x + size, self.coord.y + size, top),
+                grid.project(self.coord.x + size, self.coord.y, top),
+                grid.project(self.coord.x, self.coord.y, top),
+            ];
+            let center = self.bounds.center();
+
+            outline(corners);
+            renderer.set_fill_style(&shade([0.0, 0.0, 1.0], [center.x, center.y, top]));
+            renderer.fill(FillRule::NonZero);
+
+            draw_shadows(&Surface {
+                axis: 2,
+                at: top,
+                facing: 1.0,
+                rect: self.bounds,
+            });
+
+            outline(corners);
+            stroke();
         }
 
         // Draw character on top face
         {
-            let text_position =
-                grid.project(self.coord.x + size / 2.0, self.coord.y + size / 2.0, size);
-            context.translate(text_position.x, text_position.y);
-            context.scale(1.0, 0.5);
-            context.rotate(-std::f64::consts::PI / 4.0);
-            context.set_font(&format!("{}px sans-serif", self.size));
-            context.set_text_align("center");
-            context.set_text_baseline("middle");
-            context.set_fill_style(&JsValue::from_str("#294252"));
-            context.fill_text(&self.character.to_string(), 0.0, 0.0);
+            renderer.set_transform(grid.flat_text_transform(
+                self.coord.x + size / 2.0,
+                self.coord.y + size / 2.0,
+                top,
+            ));
+            renderer.set_font(&format!("{}px sans-serif", self.size));
+            renderer.set_text_align(TextAlign::Center);
+            renderer.set_text_baseline(TextBaseline::Middle);
+            renderer.set_fill_style("#294252");
+            renderer.fill_text(&self.character.to_string(), 0.0, 0.0);
         }
 
-        context.reset_transform();
+        renderer.reset_transform();
     }
 
-    pub fn shadow(&self, grid: &Grid) -> Option<Polygon> {
-        let corners = self.corners();
-
-        let extrapolate = |line: Line| {
-            let x_delta = line.end.x - line.start.x;
+    // Finds where a point on screen (in canvas pixels) lands on this cube, if it does.
+    // Each visible face is intersected with the line of sight through that point.
+    pub fn pick(&self, screen: Coord, grid: &Grid) -> Option<Hit> {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let (bottom, top) = (self.z, self.top());
+
+        let on_top = grid.unproject_at_z(screen, top);
+        if (min.x..=max.x).contains(&on_top.x) && (min.y..=max.y).contains(&on_top.y) {
+            return Some(Hit {
+                face: Face::Top,
+                x: on_top.x,
+                y: on_top.y,
+                z: top,
+            });
+        }
 
-            let end = Coord {
-                x: line.start.x + x_delta * 100.0,
-                y: line.start.y + (x_delta * line.slope()) * 100.0,
+        for side in self.visible_sides(grid) {
+            let (x, y, z) = if side.normal.x != 0.0 {
+                let (y, z) = grid.unproject_at_x(screen, side.start.x);
+                (side.start.x, y, z)
+            } else {
+                let (x, z) = grid.unproject_at_y(screen, side.start.y);
+                (x, side.start.y, z)
             };
 
-            Line::new(line.start, end)
-        };
-
-        // gl = ground line
-        let g_light = grid.project(grid.light_source.x, grid.light_source.y, 0.0);
-        let gl_tl: Line = extrapolate(Line::new(
-            g_light,
-            grid.project(corners.tl.x, corners.tl.y, 0.0),
-        ));
-        let gl_tr: Line = extrapolate(Line::new(
-            g_light,
-            grid.project(corners.tr.x, corners.tr.y, 0.0),
-        ));
-        let gl_br: Line = extrapolate(Line::new(
-            g_light,
-            grid.project(corners.br.x, corners.br.y, 0.0),
-        ));
-        let gl_bl: Line = extrapolate(Line::new(
-            g_light,
-            grid.project(corners.bl.x, corners.bl.y, 0.0),
-        ));
-
-        let a_light = grid.project(
-            grid.light_source.x,
-            grid.light_source.y,
-            grid.light_source.z,
-        );
-        let al_tl: Line = extrapolate(Line::new(
-            a_light,
-            grid.project(corners.tl.x, corners.tl.y, self.size),
-        ));
-        let al_tr: Line = extrapolate(Line::new(
-            a_light,
-            grid.project(corners.tr.x, corners.tr.y, self.size),
-        ));
-        let al_br: Line = extrapolate(Line::new(
-            a_light,
-            grid.project(corners.br.x, corners.br.y, self.size),
-        ));
-        let al_bl: Line = extrapolate(Line::new(
-            a_light,
-            grid.project(corners.bl.x, corners.bl.y, self.size),
-        ));
-
-        let ix_tl = geo::algorithm::line_intersection::line_intersection(gl_tl, al_tl);
-        let ix_tr = geo::algorithm::line_intersection::line_intersection(gl_tr, al_tr);
-        let ix_bl = geo::algorithm::line_intersection::line_intersection(gl_bl, al_bl);
-        let ix_br = geo::algorithm::line_intersection::line_intersection(gl_br, al_br);
-
-        match (ix_tl, ix_tr, ix_bl, ix_br) {
-            (
-                Some(LineIntersection::SinglePoint {
-                    intersection: ix_tl,
-                    ..
-                }),
-                Some(LineIntersection::SinglePoint {
-                    intersection: ix_tr,
-                    ..
-                }),
-                Some(LineIntersection::SinglePoint {
-                    intersection: ix_bl,
-                    ..
-                }),
-                Some(LineIntersection::SinglePoint {
-                    intersection: ix_br,
-                    ..
-                }),
-            ) => {
-                let tl = grid.project(corners.tl.x, corners.tl.y, 0.0);
-                let tr = grid.project(corners.tr.x, corners.tr.y, 0.0);
-                let bl = grid.project(corners.bl.x, corners.bl.y, 0.0);
-                let br = grid.project(corners.br.x, corners.br.y, 0.0);
-                let x_range = self.coord.x..(self.coord.x + self.size);
-                let y_range = self.coord.y..(self.coord.y + self.size);
-
-                let ls = &grid.light_source;
-
-                let center = self.bounds.center();
-
-                match (x_range.contains(&ls.x), y_range.contains(&ls.y)) {
-                    (true, true) => Some(polygon![ix_tl, ix_tr, ix_br, ix_bl]),
-                    (true, false) => {
-                        if center.y > ls.y {
-                            Some(polygon![tr, ix_tr, ix_br, ix_bl, ix_tl, tl])
-                        } else {
-                            Some(polygon![br, ix_br, ix_tr, ix_tl, ix_bl, bl])
-                        }
-                    }
-                    (false, true) => {
-                        if center.x > ls.x {
-                            Some(polygon![tl, ix_tl, ix_tr, ix_br, ix_bl, bl])
-                        } else {
-                            Some(polygon![tr, ix_tr, ix_tl, ix_bl, ix_br, br])
-                        }
-                    }
-                    (false, false) => {
-                        match (
-                            center.x.partial_cmp(&ls.x).unwrap(),
-                            center.y.partial_cmp(&ls.y).unwrap(),
-                        ) {
-                            (Ordering::Greater, Ordering::Greater) => {
-                                Some(polygon![tr, ix_tr, ix_br, ix_bl, bl])
-                            }
-                            (Ordering::Greater, Ordering::Less) => {
-                                Some(polygon![br, ix_br, ix_tr, ix_tl, tl])
-                            }
-                            (Ordering::Less, Ordering::Less) => {
-                                Some(polygon![tr, ix_tr, ix_tl, ix_bl, bl])
-                            }
-                            (Ordering::Less, Ordering::Greater) => {
-                                Some(polygon![br, ix_br, ix_bl, ix_tl, tl])
-                            }
-                            _ => unreachable!(),
-                        }
-                    }
-                }
-            }
-            (_tl, _tr, _bl, _br) => {
-                // TODO handle collinear intersections (rare)
-                None
+            if (min.x..=max.x).contains(&x)
+                && (min.y..=max.y).contains(&y)
+                && (bottom..=top).contains(&z)
+            {
+                return Some(Hit {
+                    face: side.face,
+                    x,
+                    y,
+                    z,
+                });
             }
         }
+
+        None
     }
 
-    pub fn hit_test(&self, posn: &Coord, grid: &Grid) -> bool {
-        let Coord { x, y } = grid.project(self.coord.x, self.coord.y, 0.0);
-
-        let edges = polygon![
-            (x: x, y: y - self.size),
-            (x: x + self.size, y: y - self.size / 2.0),
-            (x: x + self.size, y: y + self.size / 2.0),
-            (x: x, y: y + self.size),
-            (x: x - self.size, y: y + self.size / 2.0),
-            (x: x - self.size, y: y - self.size / 2.0),
-        ];
-
-        edges.contains(&Coord {
-            x: posn.x * grid.device_pixel_ratio,
-            y: posn.y * grid.device_pixel_ratio,
-        })
+    // The sides which face the viewer: two of them, or just one when the view is turned
+    // square on to the cube
+    fn visible_sides(&self, grid: &Grid) -> Vec<Side> {
+        let (min, max) = (self.bounds.min(), self.bounds.max());
+        let view = grid.view_direction();
+
+        let side = |face, normal: (f64, f64), start: (f64, f64), end: (f64, f64)| Side {
+            face,
+            normal: Coord {
+                x: normal.0,
+                y: normal.1,
+            },
+            start: Coord {
+                x: start.0,
+                y: start.1,
+            },
+            end: Coord { x: end.0, y: end.1 },
+        };
+
+        [
+            side(Face::MaxY, (0.0, 1.0), (max.x, max.y), (min.x, max.y)),
+            side(Face::MaxX, (1.0, 0.0), (max.x, max.y), (max.x, min.y)),
+            side(Face::MinY, (0.0, -1.0), (min.x, min.y), (max.x, min.y)),
+            side(Face::MinX, (-1.0, 0.0), (min.x, min.y), (min.x, max.y)),
+        ]
+        .into_iter()
+        .filter(|side| side.normal.x * view.x + side.normal.y * view.y > 0.0)
+        .collect()
     }
 
     pub fn corners(&self) -> Corners {
@@ -320,7 +344,31 @@ impl Cube {
         self
     }
 
+    #[cfg(test)]
+    pub fn character(&self) -> char {
+        self.character
+    }
+
+    pub fn top(&self) -> f64 {
+        self.z + self.size
+    }
+
+    // Whether the two cubes share any height, meaning they can bump into each other
+    pub fn overlaps_vertically(&self, other: &Cube) -> bool {
+        self.z < other.top() && other.z < self.top()
+    }
+
+    // Overlap between the two cubes, or nothing if one is entirely above the other
     pub fn overlap(&self, other: &Cube) -> (f64, f64) {
+        if self.overlaps_vertically(other) {
+            self.footprint_overlap(other)
+        } else {
+            (0.0, 0.0)
+        }
+    }
+
+    // Overlap between the two cubes as seen from above, regardless of their heights
+    pub fn footprint_overlap(&self, other: &Cube) -> (f64, f64) {
         let x_overlap = if self.bounds.max().x > other.coord.x && self.coord.x <= other.coord.x {
             self.bounds.max().x - other.coord.x
         } else if other.bounds.max().x > self.coord.x && other.coord.x <= self.coord.x {
@@ -367,4 +415,19 @@ mod tests {
         let b = Cube::new('B', 5.0, 5.0, 100.0);
         assert_eq!(a.overlap(&b), (0.0, 0.0));
     }
+
+    #[test]
+    fn test_cube_overlap_stacked() {
+        let mut a = Cube::new('A', 0.0, 0.0, 100.0);
+        let b = Cube::new('B', 5.0, 7.0, 100.0);
+
+        // Sitting right on top of b
+        a.z = 100.0;
+        assert_eq!(a.overlap(&b), (0.0, 0.0));
+        assert_eq!(a.footprint_overlap(&b), (95.0, 93.));
+
+        // Sunk halfway into b
+        a.z = 50.0;
+        assert_eq!(a.overlap(&b), (95.0, 93.));
+    }
 }
@@ -1,56 +1,63 @@
-use geo::CoordsIter;
-use geo::{Coord, Polygon};
+use geo::Coord;
 use std::cell::RefCell;
-use std::collections::HashSet;
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
-use crate::cube::Cube;
-use crate::grid::Grid;
+use crate::camera;
 use crate::link::Link;
-use crate::movement::Movement;
-
+use crate::momentum;
+use crate::persistence::{self, SavedLayout};
+use crate::pointer::{PointerInput, PointerKind};
+use crate::render::Canvas2d;
+use crate::router;
+use crate::scene::Scene;
+use crate::svg::Svg;
+use crate::world::{Input, World};
+
+// The browser side of things: a canvas, the event listeners feeding it, and a render
+// loop which steps the World and draws whatever it ends up looking like.
 pub struct CubeGrid {
-    cubes: Vec<Cube>,
-    grid: Grid,
+    world: World,
 
-    links: Vec<Link>,
+    // The path of the scene being shown
+    path: String,
 
-    next_id: usize,
+    last_frame_time: Option<f64>,
 
     canvas: web_sys::HtmlCanvasElement,
     context: Rc<web_sys::CanvasRenderingContext2d>,
 
     window_dimensions: Rc<RefCell<WindowDimensions>>,
 
-    touch_events: Rc<RefCell<Vec<web_sys::TouchEvent>>>,
-    mouse_events: Rc<RefCell<Vec<MouseEvent>>>,
-    mouse_state: MouseState,
+    // Added to by canvas event listeners, consumed on the next frame
+    inputs: Rc<RefCell<Vec<Input>>>,
+
+    // Paths the browser went back or forward to, also consumed on the next frame
+    navigations: Rc<RefCell<Vec<String>>>,
+
+    // When to save the layout, once the cubes have been left alone for a bit
+    pending_save: Option<f64>,
 }
 
-#[derive(Default)]
-enum Cursor {
-    #[default]
-    Default,
-    Grab,
-    Link,
+// Things which can be asked of the page from outside the canvas: the keyboard,
+// or JS calling one of the functions exported from lib.rs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    ResetLayout,
+    DownloadSvg,
 }
 
-impl Cursor {
-    fn to_css_string(&self, is_active: bool) -> &'static str {
-        match (self, is_active) {
-            (Cursor::Default, _) => "default",
-            (Cursor::Grab, false) => "grab",
-            (Cursor::Grab, true) => "grabbing",
-            (Cursor::Link, _) => "pointer",
-        }
-    }
+thread_local! {
+    static COMMANDS: RefCell<Vec<Command>> = const { RefCell::new(Vec::new()) };
 }
 
-#[derive(Clone, Copy)]
-struct MouseEvent {
-    is_pressed: bool,
-    position: Coord,
+// Queues a command for the next frame
+pub fn queue_command(command: Command) {
+    COMMANDS.with(|commands| commands.borrow_mut().push(command));
+}
+
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
 }
 
 #[derive(Default)]
@@ -79,43 +86,58 @@ fn window_dimens() -> (f64, f64, f64) {
     (width, height, device_pixel_ratio)
 }
 
-impl From<web_sys::MouseEvent> for MouseEvent {
-    fn from(event: web_sys::MouseEvent) -> Self {
-        Self {
-            is_pressed: event.buttons() == 1,
-            position: Coord {
-                x: event.client_x() as f64,
-                y: event.client_y() as f64,
-            },
-        }
-    }
-}
+// The local time, for the light to follow in Day mode
+fn clock_input() -> Input {
+    let now = js_sys::Date::new_0();
+    let hours = now.get_hours() as f64
+        + now.get_minutes() as f64 / 60.0
+        + now.get_seconds() as f64 / 3600.0;
 
-#[derive(Default)]
-struct MouseState {
-    last_position: Coord,
-    last_down_position: Coord,
-    current_position: Coord,
-
-    // When this is running on a touch device, we track
-    // the first touch and only use that (no multitouch support for now!)
-    current_touch: Option<i32>,
-
-    is_pressed: bool,
-    is_dragging_cube: bool,
-    just_released: bool,
+    Input::Clock { hours }
+}
 
-    cursor_style: Cursor,
+// Hands some text to the browser to save as a file
+fn download(file_name: &str, contents: &str, mime_type: &str) -> Result<(), JsValue> {
+    let parts = js_sys::Array::of1(&JsValue::from_str(contents));
+    let options = web_sys::BlobPropertyBag::new();
+    options.set_type(mime_type);
+    let blob = web_sys::Blob::new_with_str_sequence_and_options(&parts, &options)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+
+    let document = web_sys::window().unwrap().document().unwrap();
+    let link = document
+        .create_element("a")?
+        .dyn_into::<web_sys::HtmlAnchorElement>()?;
+    link.set_href(&url);
+    link.set_download(file_name);
+    link.click();
+
+    web_sys::Url::revoke_object_url(&url)
 }
 
-impl MouseState {
-    fn cursor_string(&self) -> &'static str {
-        self.cursor_style.to_css_string(self.is_pressed)
-    }
+fn pointer_input(event: &web_sys::PointerEvent) -> Input {
+    let kind = match event.pointer_type().as_str() {
+        "touch" => PointerKind::Touch,
+        "pen" => PointerKind::Pen,
+        _ => PointerKind::Mouse,
+    };
+
+    Input::Pointer(PointerInput {
+        id: event.pointer_id(),
+        kind,
+        // The primary button, or a finger or pen touching the screen
+        is_pressed: event.buttons() & 1 == 1,
+        position: Coord {
+            x: event.client_x() as f64,
+            y: event.client_y() as f64,
+        },
+        time: event.time_stamp(),
+        shift_key: event.shift_key(),
+    })
 }
 
 impl CubeGrid {
-    pub fn new(canvas: web_sys::HtmlCanvasElement) -> Self {
+    pub fn new(canvas: web_sys::HtmlCanvasElement, path: String) -> Self {
         let context = canvas
             .get_context("2d")
             .unwrap()
@@ -125,77 +147,228 @@ impl CubeGrid {
         let context = Rc::new(context);
 
         CubeGrid {
-            next_id: 1,
-
-            grid: Grid::default(),
+            world: World::new(),
+            path,
 
-            cubes: Vec::new(),
-            links: Vec::new(),
+            last_frame_time: None,
 
             canvas,
             context,
 
-            // Added to by canvas event listeners, consumed in handle_mouse_events
-            mouse_events: Rc::new(RefCell::new(Vec::new())),
-            touch_events: Rc::new(RefCell::new(Vec::new())),
             window_dimensions: Rc::new(RefCell::new(WindowDimensions::default())),
 
-            mouse_state: MouseState::default(),
+            inputs: Rc::new(RefCell::new(Vec::new())),
+            navigations: Rc::new(RefCell::new(Vec::new())),
+
+            pending_save: None,
         }
     }
 
-    pub fn insert_cubes(&mut self, cubes: Vec<Cube>) {
-        for mut cube in cubes {
-            cube.id = self.next_id;
-            self.next_id += 1;
-            self.cubes.push(cube);
+    pub fn load_scene(&mut self, scene: Scene, animate: bool) {
+        let links = scene
+            .links
+            .iter()
+            .map(|link| {
+                let width = Link::measure_width(&link.text, &self.context);
+                Link::new(link.x, link.y, link.text.clone(), link.url.clone(), width)
+            })
+            .collect();
+
+        let saved = local_storage()
+            .and_then(|storage| {
+                storage
+                    .get_item(&persistence::storage_key(&self.path))
+                    .ok()?
+            })
+            .and_then(|json| SavedLayout::from_json(&json, scene.hash));
+
+        self.world
+            .load_scene(&scene, links, saved.as_ref(), animate);
+    }
+
+    // Once the spring has brought everything home, the save is cleared instead, so a
+    // scattered layout doesn't come back on the next visit just to be pulled home again
+    fn save_layout(&mut self) {
+        self.pending_save = None;
+
+        if let Some(storage) = local_storage() {
+            let key = persistence::storage_key(&self.path);
+            if self.world.is_home() {
+                let _ = storage.remove_item(&key);
+            } else {
+                let json = self.world.saved_layout().to_json();
+                let _ = storage.set_item(&key, &json);
+            }
+        }
+    }
+
+    fn reset_layout(&mut self) {
+        self.pending_save = None;
+        self.world.reset_layout();
+
+        if let Some(storage) = local_storage() {
+            let _ = storage.remove_item(&persistence::storage_key(&self.path));
+        }
+    }
+
+    // Saves the scene as it is now to a file named after the page
+    fn download_svg(&self) {
+        let svg = Svg::new();
+        self.world.draw_scene(&svg);
+
+        let name = match self.path.trim_matches('/') {
+            "" => "home".to_string(),
+            path => path.replace('/', "-"),
+        };
+
+        if let Err(error) = download(&format!("{}.svg", name), &svg.finish(), "image/svg+xml") {
+            console_log!("Couldn't download the SVG: {:?}", error);
         }
     }
 
-    pub fn insert_link(&mut self, x: f64, y: f64, label: &'static str, url: &'static str) {
-        self.links.push(Link::new(
-                x,y,label,url,&self.context,
-        ))
+    // Swaps to the scene at `path`, which is already in the address bar
+    fn navigate(&mut self, path: String) {
+        // Don't lose the layout of the page we're leaving
+        if self.pending_save.is_some() {
+            self.save_layout();
+        }
+
+        match Scene::route(&path) {
+            Ok(scene) => {
+                self.path = path;
+                self.load_scene(scene, true);
+            }
+            Err(error) => {
+                console_log!("Couldn't load scene: {}", error);
+            }
+        }
+    }
+
+    // Follows a link, in place if it's to one of our scenes
+    fn follow_link(&mut self, url: &str) {
+        let window = web_sys::window().unwrap();
+
+        match router::internal_path(&self.path, url) {
+            Some(path) => {
+                let _ = window.history().and_then(|history| {
+                    history.push_state_with_url(&JsValue::NULL, "", Some(&path))
+                });
+                self.navigate(path);
+            }
+            None => {
+                let _ = window.open_with_url_and_target(url, "_top");
+            }
+        }
     }
 
     #[allow(unused_must_use)]
     pub fn start(mut self) -> Result<(), JsValue> {
-        // Handle mouse events
+        // Mouse, pen and touch all come through as pointer events
         {
-            let mouse_events = self.mouse_events.clone();
-            let closure = Closure::<dyn FnMut(_)>::new(move |event: web_sys::MouseEvent| {
-                mouse_events.borrow_mut().push(event.into());
+            let inputs = self.inputs.clone();
+            let canvas = self.canvas.clone();
+            let closure = Closure::<dyn FnMut(_)>::new(move |event: web_sys::PointerEvent| {
+                // Keep getting events for this pointer even once it leaves the canvas,
+                // so a drag doesn't get stuck
+                if event.type_() == "pointerdown" {
+                    canvas.set_pointer_capture(event.pointer_id());
+                }
+
+                inputs.borrow_mut().push(pointer_input(&event));
             });
 
-            // Mouse events
-            self.canvas
-                .add_event_listener_with_callback("mousemove", closure.as_ref().unchecked_ref())?;
-            self.canvas
-                .add_event_listener_with_callback("mousedown", closure.as_ref().unchecked_ref())?;
-            self.canvas
-                .add_event_listener_with_callback("mouseup", closure.as_ref().unchecked_ref())?;
+            for event in ["pointerdown", "pointermove", "pointerup"] {
+                self.canvas
+                    .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())?;
+            }
 
             closure.forget();
         }
 
+        // The browser took a pointer for itself, or it was let go somewhere we'll never
+        // hear about. Either way, whatever it was dragging gets dropped.
         {
-            let touch_events = self.touch_events.clone();
-            let closure2 = Closure::<dyn FnMut(_)>::new(move |event: web_sys::TouchEvent| {
-                event.prevent_default();
-                touch_events.borrow_mut().push(event);
+            let inputs = self.inputs.clone();
+            let closure = Closure::<dyn FnMut(_)>::new(move |event: web_sys::PointerEvent| {
+                inputs.borrow_mut().push(Input::PointerCancel {
+                    id: event.pointer_id(),
+                });
+            });
+
+            for event in ["pointercancel", "lostpointercapture"] {
+                self.canvas
+                    .add_event_listener_with_callback(event, closure.as_ref().unchecked_ref())?;
+            }
+
+            closure.forget();
+        }
+
+        // Whatever the mouse was over when it left the canvas isn't hovered any more
+        {
+            let inputs = self.inputs.clone();
+            let closure = Closure::<dyn FnMut()>::new(move || {
+                inputs.borrow_mut().push(Input::PointerLeave);
             });
 
-            // Touch events
             self.canvas.add_event_listener_with_callback(
-                "touchstart",
-                closure2.as_ref().unchecked_ref(),
+                "pointerleave",
+                closure.as_ref().unchecked_ref(),
             )?;
+
+            closure.forget();
+        }
+
+        // Scrolling pans, and pinching a trackpad (which comes through as scrolling with
+        // ctrl held) zooms in around the pointer
+        {
+            let inputs = self.inputs.clone();
+            let closure = Closure::<dyn FnMut(_)>::new(move |event: web_sys::WheelEvent| {
+                event.prevent_default();
+
+                // Some mice scroll by lines rather than pixels
+                let scale = match event.delta_mode() {
+                    web_sys::WheelEvent::DOM_DELTA_LINE => 16.0,
+                    web_sys::WheelEvent::DOM_DELTA_PAGE => 400.0,
+                    _ => 1.0,
+                };
+                let (dx, dy) = (event.delta_x() * scale, event.delta_y() * scale);
+
+                let input = if event.ctrl_key() {
+                    Input::Zoom {
+                        factor: (-dy * camera::WHEEL_ZOOM_SPEED).exp(),
+                        position: Coord {
+                            x: event.client_x() as f64,
+                            y: event.client_y() as f64,
+                        },
+                    }
+                } else {
+                    Input::Pan { dx: -dx, dy: -dy }
+                };
+                inputs.borrow_mut().push(input);
+            });
+
+            // Not passive, or the page would zoom along with us
+            let options = web_sys::AddEventListenerOptions::new();
+            options.set_passive(false);
             self.canvas
-                .add_event_listener_with_callback("touchmove", closure2.as_ref().unchecked_ref())?;
-            self.canvas
-                .add_event_listener_with_callback("touchend", closure2.as_ref().unchecked_ref())?;
+                .add_event_listener_with_callback_and_add_event_listener_options(
+                    "wheel",
+                    closure.as_ref().unchecked_ref(),
+                    &options,
+                )?;
+            closure.forget();
+        }
+
+        {
+            let inputs = self.inputs.clone();
+            let closure = Closure::<dyn FnMut()>::new(move || {
+                inputs.borrow_mut().push(Input::Blur);
+            });
 
-            closure2.forget();
+            web_sys::window()
+                .unwrap()
+                .add_event_listener_with_callback("blur", closure.as_ref().unchecked_ref())?;
+            closure.forget();
         }
 
         // Handle window resize events
@@ -229,295 +402,176 @@ impl CubeGrid {
             closure.forget();
         }
 
-        // Main render loop
+        // Keyboard shortcuts
         {
-            let f = Rc::new(RefCell::new(None));
-            let g = f.clone();
-            let context = self.context.clone();
-
-            // Initial render
-            self.sort_cubes();
-            self.update_dimens();
-            self.draw(&context);
-
-            *g.borrow_mut() = Some(Closure::new(move || {
-                // Schedule ourself for another requestAnimationFrame callback.
-                /*
-                let performance = web_sys::window()
-                    .unwrap()
-                    .performance()
-                    .expect("performance should be available");
-
-                let now = performance.now();
-                let offset = (now % 3000.0) / 10.0;
-                */
-
-                let events_changed = self.handle_input_events();
-                let dimens_changed = self.update_dimens();
-
-                if dimens_changed || events_changed {
-                    self.handle_mouse_state();
-                    self.sort_cubes();
-                    self.draw(&context);
+            let inputs = self.inputs.clone();
+            let window_dimensions = self.window_dimensions.clone();
+            let closure = Closure::<dyn FnMut(_)>::new(move |event: web_sys::KeyboardEvent| {
+                if event.ctrl_key() || event.meta_key() || event.alt_key() {
+                    return;
                 }
 
-                self.canvas
-                    .style()
-                    .set_property("cursor", self.mouse_state.cursor_string());
-
-                request_animation_frame(f.borrow().as_ref().unwrap());
-            }));
-
-            request_animation_frame(g.borrow().as_ref().unwrap());
-        }
-
-        Ok(())
-    }
-
-    pub fn update_dimens(&mut self) -> bool {
-        let dimens = self.window_dimensions.borrow();
-        self.grid
-            .update_dimensions(dimens.width, dimens.height, dimens.device_pixel_ratio)
-    }
-
-    pub fn handle_input_events(&mut self) -> bool {
-        let mut mouse_events = self.mouse_events.borrow_mut();
-        let mut touch_events = self.touch_events.borrow_mut();
-
-        if !mouse_events.is_empty() {
-            self.mouse_state.last_position = self.mouse_state.current_position;
-
-            self.mouse_state.just_released = false;
-
-            for event in mouse_events.iter() {
-                self.mouse_state.current_position = event.position;
-
-                if event.is_pressed && !self.mouse_state.is_pressed {
-                    self.mouse_state.is_pressed = true;
-                    self.mouse_state.last_down_position = event.position;
-                } else if !event.is_pressed {
-                    // released after click
-                    if self.mouse_state.is_pressed {
-                        self.mouse_state.just_released = true;
+                // Zooming from the keyboard goes around the middle of the screen
+                let middle = {
+                    let dimens = window_dimensions.borrow();
+                    Coord {
+                        x: dimens.width / 2.0,
+                        y: dimens.height / 2.0,
                     }
-
-                    self.mouse_state.is_pressed = false;
-                }
-            }
-
-            *mouse_events = Vec::new();
-
-            true
-        } else if !touch_events.is_empty() {
-            self.mouse_state.last_position = self.mouse_state.current_position;
-
-            for event in touch_events.iter() {
-                let touches = event.touches();
-
-                match touches.get(0) {
-                    Some(touch) => {
-                        let id = touch.identifier();
-                        if let Some(current_touch) = self.mouse_state.current_touch {
-                            if id == current_touch {
-                                self.mouse_state.is_pressed = true;
-                                self.mouse_state.current_position = Coord {
-                                    x: touch.client_x() as f64,
-                                    y: touch.client_y() as f64,
-                                }
-                            } else {
-                                // The currently tracked touch disappeared and we're left with
-                                // a different one. Don't do anything until we reset to 0
-                                // touches.
-                            }
-                        } else {
-                            self.mouse_state.current_touch = Some(id);
-                            let mp: Coord = Coord {
-                                x: touch.client_x() as f64,
-                                y: touch.client_y() as f64,
-                            };
-                            self.mouse_state.current_position = mp;
-                            self.mouse_state.last_down_position = mp;
-                            self.mouse_state.is_pressed = true;
-                        }
+                };
+                let zoom = |factor| Input::Zoom {
+                    factor,
+                    position: middle,
+                };
+
+                let input = match event.key().as_str() {
+                    "r" | "R" => {
+                        queue_command(Command::ResetLayout);
+                        return;
                     }
-                    None => {
-                        // Touch interaction was released
-                        if self.mouse_state.current_touch.is_some() {
-                            self.mouse_state.current_touch = None;
-                            self.mouse_state.just_released = true;
-                            self.mouse_state.is_pressed = false;
-                        }
+                    "s" | "S" => {
+                        queue_command(Command::DownloadSvg);
+                        return;
                     }
-                }
-            }
+                    "ArrowLeft" => Input::Pan {
+                        dx: camera::PAN_STEP,
+                        dy: 0.0,
+                    },
+                    "ArrowRight" => Input::Pan {
+                        dx: -camera::PAN_STEP,
+                        dy: 0.0,
+                    },
+                    "ArrowUp" => Input::Pan {
+                        dx: 0.0,
+                        dy: camera::PAN_STEP,
+                    },
+                    "ArrowDown" => Input::Pan {
+                        dx: 0.0,
+                        dy: -camera::PAN_STEP,
+                    },
+                    "+" | "=" => zoom(camera::ZOOM_STEP),
+                    "-" => zoom(1.0 / camera::ZOOM_STEP),
+                    "0" => Input::ResetCamera,
+                    "q" | "Q" => Input::Rotate { quarter_turns: -1 },
+                    "e" | "E" => Input::Rotate { quarter_turns: 1 },
+                    "l" | "L" => Input::NextLightMode,
+                    _ => return,
+                };
 
-            *touch_events = Vec::new();
+                event.prevent_default();
+                inputs.borrow_mut().push(input);
+            });
 
-            true
-        } else {
-            false
+            web_sys::window()
+                .unwrap()
+                .add_event_listener_with_callback("keydown", closure.as_ref().unchecked_ref())?;
+            closure.forget();
         }
-    }
 
-    fn sort_cubes(&mut self) {
-        self.cubes.sort_by(|a, b| {
-            let a = self
-                .grid
-                .project(a.coord.x + a.size / 2.0, a.coord.y + a.size / 2.0, 0.0);
-            let b = self
-                .grid
-                .project(b.coord.x + b.size / 2.0, b.coord.y + b.size / 2.0, 0.0);
-            b.y.partial_cmp(&a.y).unwrap()
-        });
-    }
+        // Handle back and forward
+        {
+            let navigations = self.navigations.clone();
+            let closure = Closure::<dyn FnMut(_)>::new(move |_: web_sys::PopStateEvent| {
+                if let Ok(path) = web_sys::window().unwrap().location().pathname() {
+                    navigations.borrow_mut().push(path);
+                }
+            });
 
-    fn handle_mouse_state(&mut self) {
-        if self.mouse_state.is_pressed && self.mouse_state.is_dragging_cube {
-            // Actively dragging a cube
-            let (current_iso_x, current_iso_y) = self.grid.cartesian_to_iso(
-                self.mouse_state.current_position.x,
-                self.mouse_state.current_position.y,
-            );
-            let (last_iso_x, last_iso_y) = self.grid.cartesian_to_iso(
-                self.mouse_state.last_position.x,
-                self.mouse_state.last_position.y,
-            );
-
-            let dx = current_iso_x - last_iso_x;
-            let dy = current_iso_y - last_iso_y;
-
-            if dx == 0.0 && dy == 0.0 {
-                return;
-            }
+            web_sys::window()
+                .unwrap()
+                .add_event_listener_with_callback("popstate", closure.as_ref().unchecked_ref())?;
+            closure.forget();
+        }
 
-            let mut cubes_moved: HashSet<usize> = HashSet::new();
-            let mut cubes_to_move: Vec<Movement> = Vec::new();
+        // Main render loop
+        {
+            let f = Rc::new(RefCell::new(None));
+            let g = f.clone();
+            let context = self.context.clone();
 
-            for cube in &mut self.cubes {
-                if cube.is_active {
-                    cubes_to_move.push(Movement::new_from_delta(*cube, dx, dy));
-                }
-            }
+            // Initial render
+            let resize = self.resize_input();
+            self.world.step(&[resize], 0.0);
+            self.world.draw(&Canvas2d::new(&context));
 
-            while let Some(movement) = cubes_to_move.pop() {
-                let mut last_movement: Option<Movement> = None;
+            let performance = web_sys::window()
+                .unwrap()
+                .performance()
+                .expect("performance should be available");
 
-                for cube in &mut self.cubes {
-                    if cube.id == movement.end.id {
-                        cube.translate(movement.dx, movement.dy);
+            *g.borrow_mut() = Some(Closure::new(move || {
+                let now = performance.now();
+                let dt = match self.last_frame_time {
+                    Some(last_frame_time) => (now - last_frame_time).min(momentum::MAX_FRAME_TIME),
+                    None => 0.0,
+                };
+                self.last_frame_time = Some(now);
 
-                        cubes_moved.insert(cube.id);
+                let mut inputs: Vec<Input> = self.inputs.borrow_mut().drain(..).collect();
+                inputs.push(self.resize_input());
+                inputs.push(clock_input());
 
-                        last_movement = Some(movement);
+                let frame = self.world.step(&inputs, dt);
 
-                        break;
-                    }
+                if frame.redraw {
+                    self.world.draw(&Canvas2d::new(&context));
                 }
 
-                if let Some(last_movement) = last_movement {
-                    // check other overlapping cubes
-                    for other_cube in &self.cubes {
-                        if !cubes_moved.contains(&other_cube.id) {
-                            if let Some(impact) = last_movement.impact(*other_cube) {
-                                cubes_to_move.push(impact);
-                            }
-                        }
-                    }
+                if !frame.unresolved.is_empty() {
+                    console_log!(
+                        "Gave up resolving overlapping cubes: {:?}",
+                        frame.unresolved
+                    );
                 }
-            }
-        } else if !self.mouse_state.is_pressed && self.mouse_state.is_dragging_cube {
-            // Just stopped dragging
-            for cube in &mut self.cubes {
-                if cube.is_active {
-                    self.mouse_state.is_dragging_cube = false;
+
+                if let Some(url) = frame.open_url {
+                    self.follow_link(&url);
                 }
-            }
-        } else {
-            // Not dragging anything, just moving cursor around
-            let mut found_active_object = false;
-            self.mouse_state.is_dragging_cube = false;
-
-            for cube in &mut self.cubes {
-                if !found_active_object
-                    && cube.hit_test(&self.mouse_state.current_position, &self.grid)
-                {
-                    cube.is_active = true;
-                    found_active_object = true;
 
-                    self.mouse_state.cursor_style = Cursor::Grab;
+                let navigations: Vec<String> = self.navigations.borrow_mut().drain(..).collect();
+                for path in navigations {
+                    self.navigate(path);
+                }
 
-                    if self.mouse_state.is_pressed {
-                        self.mouse_state.is_dragging_cube = true;
+                let commands: Vec<Command> =
+                    COMMANDS.with(|commands| commands.borrow_mut().drain(..).collect());
+                for command in commands {
+                    match command {
+                        Command::ResetLayout => self.reset_layout(),
+                        Command::DownloadSvg => self.download_svg(),
                     }
-                } else {
-                    cube.is_active = false;
                 }
-            }
 
-            for link in &mut self.links {
-                if !found_active_object
-                    && link.hit_test(&self.mouse_state.current_position, &self.grid)
+                if frame.layout_settled {
+                    self.pending_save = Some(now + persistence::SAVE_DELAY);
+                }
+                if self
+                    .pending_save
+                    .is_some_and(|deadline| now >= deadline && !self.world.is_dragging())
                 {
-                    found_active_object = true;
-
-                    self.mouse_state.cursor_style = Cursor::Link;
-
-                    link.is_active = !self.mouse_state.is_pressed;
-
-                    if self.mouse_state.just_released {
-                        let _ = web_sys::window()
-                            .unwrap()
-                            .open_with_url_and_target(link.url, "_top");
-                    }
-                } else {
-                    link.is_active = false;
+                    self.save_layout();
                 }
-            }
 
-            if !found_active_object {
-                self.mouse_state.cursor_style = Cursor::Default;
-            }
-        }
-    }
-
-    fn draw(&mut self, context: &web_sys::CanvasRenderingContext2d) {
-        context.clear_rect(
-            0.0,
-            0.0,
-            self.grid.width * self.grid.device_pixel_ratio,
-            self.grid.height * self.grid.device_pixel_ratio,
-        );
+                self.canvas.style().set_property("cursor", frame.cursor);
 
-        self.draw_shadow(context);
+                // Schedule ourself for another requestAnimationFrame callback.
+                request_animation_frame(f.borrow().as_ref().unwrap());
+            }));
 
-        for link in &self.links {
-            link.draw(context, &self.grid);
+            request_animation_frame(g.borrow().as_ref().unwrap());
         }
 
-        for cube in self.cubes.iter().rev() {
-            cube.draw(context, &self.grid);
-        }
+        Ok(())
     }
 
-    fn draw_shadow(&mut self, context: &web_sys::CanvasRenderingContext2d) {
-        let shadows: Vec<Polygon> = self
-            .cubes
-            .iter()
-            .flat_map(|cube| cube.shadow(&self.grid))
-            .collect();
-
-        context.set_fill_style(&JsValue::from_str("rgba(245, 245, 245, 1.0)"));
+    // The window size goes in with every frame; the world only redraws if it changed
+    fn resize_input(&self) -> Input {
+        let dimens = self.window_dimensions.borrow();
 
-        for shadow in shadows {
-            context.begin_path();
-            for (i, coord) in shadow.coords_iter().enumerate() {
-                if i == 0 {
-                    context.move_to(coord.x, coord.y);
-                } else {
-                    context.line_to(coord.x, coord.y);
-                }
-            }
-            context.fill();
+        Input::Resize {
+            width: dimens.width,
+            height: dimens.height,
+            device_pixel_ratio: dimens.device_pixel_ratio,
         }
     }
 }
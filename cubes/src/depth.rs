@@ -0,0 +1,223 @@
+use geo::Coord;
+use std::cmp::Ordering;
+
+use crate::Cube;
+
+// Sorts cubes into painter's order for the isometric view, front-most first
+// (so they're drawn in reverse, and picked in order).
+//
+// Comparing centers only works for cubes of the same size on the same level. Instead,
+// for every pair of cubes which overlap on screen we find an axis in world space that
+// separates them; whichever is further along that axis is closer to the viewer. That
+// gives a partial order, which we turn into a full one with a topological sort.
+//
+// `view` is which way the viewer is across the floor, per unit up (Grid::view_direction).
+pub fn sort(cubes: &mut Vec<Cube>, view: Coord) {
+    let view = [view.x, view.y, 1.0];
+    let count = cubes.len();
+
+    // in_front_of[i] lists every cube which has to be drawn after cube i
+    let mut in_front_of: Vec<Vec<usize>> = vec![vec![]; count];
+    let mut behind_count: Vec<usize> = vec![0; count];
+
+    for i in 0..count {
+        for j in (i + 1)..count {
+            if !overlap_on_screen(&cubes[i], &cubes[j], view) {
+                continue;
+            }
+
+            match compare(&cubes[i], &cubes[j], view) {
+                Ordering::Less => {
+                    in_front_of[i].push(j);
+                    behind_count[j] += 1;
+                }
+                Ordering::Greater => {
+                    in_front_of[j].push(i);
+                    behind_count[i] += 1;
+                }
+                Ordering::Equal => {}
+            }
+        }
+    }
+
+    // Kahn's algorithm. Among the cubes free to be drawn next we take the one with
+    // the furthest-back center, which keeps the order stable between frames. If we
+    // ever get stuck on a cycle (only possible when cubes intersect), we break it the
+    // same way.
+    let mut order: Vec<usize> = Vec::with_capacity(count);
+    let mut placed = vec![false; count];
+
+    while order.len() < count {
+        let candidates = (0..count).filter(|i| !placed[*i]);
+        let ready = candidates.clone().filter(|i| behind_count[*i] == 0);
+
+        let depth = |i: &usize| center_depth(&cubes[*i], view);
+        let next = ready
+            .min_by(|a, b| depth(a).total_cmp(&depth(b)))
+            .or_else(|| candidates.min_by(|a, b| depth(a).total_cmp(&depth(b))))
+            .unwrap();
+
+        placed[next] = true;
+        order.push(next);
+
+        for j in &in_front_of[next] {
+            behind_count[*j] = behind_count[*j].saturating_sub(1);
+        }
+    }
+
+    let sorted: Vec<Cube> = order.iter().rev().map(|i| cubes[*i]).collect();
+    *cubes = sorted;
+}
+
+// Whether a is behind (Less) or in front of (Greater) b, from the viewer's point of view.
+//
+// Along any line of sight, points further along `view` are closer. Unturned that's
+// (1, 1, 1), so points with a bigger x, y or z are closer. If the cubes are separated
+// along any one axis the view isn't square to, the one further towards the viewer along
+// it is in front. For cubes which overlap on screen every separating axis agrees, so it
+// doesn't matter which we find first.
+pub fn compare(a: &Cube, b: &Cube, view: [f64; 3]) -> Ordering {
+    let (a_min, a_max) = extents(a);
+    let (b_min, b_max) = extents(b);
+
+    for axis in 0..3 {
+        let towards_viewer = view[axis].partial_cmp(&0.0).unwrap_or(Ordering::Equal);
+        if towards_viewer == Ordering::Equal {
+            continue;
+        }
+
+        if a_max[axis] <= b_min[axis] {
+            return towards_viewer.reverse();
+        }
+        if b_max[axis] <= a_min[axis] {
+            return towards_viewer;
+        }
+    }
+
+    // The cubes intersect, which the solver should never leave us with
+    center_depth(a, view).total_cmp(&center_depth(b, view))
+}
+
+// A cube's outline on screen is a hexagon, whose edges run along the three world axes
+// as projected. Each pair of opposite edges bounds how far across the screen the cube
+// reaches at right angles to one axis, which for the other two axes u and v is
+// view[v] * u - view[u] * v (constant along a line of sight). Unturned, that's x - y,
+// x - z and y - z. Two outlines overlap exactly when those three ranges all overlap.
+pub fn overlap_on_screen(a: &Cube, b: &Cube, view: [f64; 3]) -> bool {
+    let (a_min, a_max) = extents(a);
+    let (b_min, b_max) = extents(b);
+
+    [(0, 1), (0, 2), (1, 2)].iter().all(|(u, v)| {
+        let range = |min: [f64; 3], max: [f64; 3]| {
+            let along_u = (view[*v] * min[*u], view[*v] * max[*u]);
+            let along_v = (-view[*u] * min[*v], -view[*u] * max[*v]);
+
+            (
+                along_u.0.min(along_u.1) + along_v.0.min(along_v.1),
+                along_u.0.max(along_u.1) + along_v.0.max(along_v.1),
+            )
+        };
+        let (a_range, b_range) = (range(a_min, a_max), range(b_min, b_max));
+
+        a_range.0 < b_range.1 && b_range.0 < a_range.1
+    })
+}
+
+fn extents(cube: &Cube) -> ([f64; 3], [f64; 3]) {
+    let min = cube.bounds.min();
+    let max = cube.bounds.max();
+
+    ([min.x, min.y, cube.z], [max.x, max.y, cube.top()])
+}
+
+fn center_depth(cube: &Cube, view: [f64; 3]) -> f64 {
+    let center = cube.bounds.center();
+    center.x * view[0] + center.y * view[1] + (cube.z + cube.size / 2.0) * view[2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::coord;
+
+    const VIEW: [f64; 3] = [1.0, 1.0, 1.0];
+
+    fn unturned() -> Coord {
+        coord! { x: 1.0, y: 1.0 }
+    }
+
+    fn cube(id: usize, x: f64, y: f64, z: f64, size: f64) -> Cube {
+        let mut cube = Cube::new('A', x, y, size);
+        cube.id = id;
+        cube.z = z;
+        cube
+    }
+
+    fn ids(cubes: &[Cube]) -> Vec<usize> {
+        cubes.iter().map(|cube| cube.id).collect()
+    }
+
+    #[test]
+    fn test_mixed_sizes() {
+        // A small cube tucked against the right face of a big one. Its center is further
+        // back than the big cube's, but it's in front of the big cube's right face.
+        let big = cube(1, 0.0, 0.0, 0.0, 100.0);
+        let small = cube(2, 100.0, -45.0, 0.0, 60.0);
+        assert!(center_depth(&small, VIEW) < center_depth(&big, VIEW));
+        assert!(overlap_on_screen(&big, &small, VIEW));
+        assert_eq!(compare(&big, &small, VIEW), Ordering::Less);
+
+        let mut cubes = vec![big, small];
+        sort(&mut cubes, unturned());
+        assert_eq!(ids(&cubes), vec![2, 1]);
+    }
+
+    #[test]
+    fn test_stacked() {
+        // A tall tower behind a cube on the floor, and a cube resting on the tower
+        let mut cubes = vec![
+            cube(1, 0.0, 0.0, 0.0, 100.0),
+            cube(2, 0.0, 0.0, 100.0, 100.0),
+            cube(3, 20.0, 120.0, 0.0, 60.0),
+            cube(4, 300.0, 300.0, 0.0, 60.0),
+        ];
+        sort(&mut cubes, unturned());
+
+        let position = |id| cubes.iter().position(|cube| cube.id == id).unwrap();
+        // Front-most first: the small floor cube covers the bottom of the tower,
+        // and the top of the tower covers its bottom
+        assert!(position(3) < position(1));
+        assert!(position(2) < position(1));
+    }
+
+    #[test]
+    fn test_not_overlapping_on_screen() {
+        // Side by side from the viewer's point of view
+        let a = cube(1, 0.0, 100.0, 0.0, 100.0);
+        let b = cube(2, 100.0, 0.0, 0.0, 100.0);
+        assert!(!overlap_on_screen(&a, &b, VIEW));
+    }
+
+    #[test]
+    fn test_turned() {
+        // Two cubes in a row along x. Unturned, the one further along x is in front...
+        let a = cube(1, 0.0, 0.0, 0.0, 100.0);
+        let b = cube(2, 100.0, 20.0, 0.0, 100.0);
+        let mut cubes = vec![a, b];
+        sort(&mut cubes, unturned());
+        assert_eq!(ids(&cubes), vec![2, 1]);
+
+        // ...and turned halfway round, the viewer is off the other way
+        sort(&mut cubes, coord! { x: -1.0, y: -1.0 });
+        assert_eq!(ids(&cubes), vec![1, 2]);
+
+        // Partway round, with the viewer straight off along x, x decides it
+        assert!(overlap_on_screen(&a, &b, [1.0, 0.0, 1.0]));
+        assert_eq!(compare(&a, &b, [1.0, 0.0, 1.0]), Ordering::Less);
+
+        // One right in front of the other unturned is side by side a quarter turn later
+        let c = cube(3, 100.0, 100.0, 0.0, 100.0);
+        assert!(overlap_on_screen(&a, &c, VIEW));
+        assert!(!overlap_on_screen(&a, &c, [1.0, -1.0, 1.0]));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::Cube;
+
+// Iso units per ms², tuned by eye rather than to any real-world scale
+pub const GRAVITY: f64 = 0.004;
+
+// How far above everything beneath it a cube floats while it's being carried
+pub const LIFT_HEIGHT: f64 = 30.0;
+
+// Slack for floating point error when deciding if a cube is resting on another
+const EPSILON: f64 = 1e-6;
+
+// Height of the highest surface directly beneath a cube that it could land on:
+// either the floor, or the top face of another cube under it.
+pub fn support_height(cube: &Cube, cubes: &[Cube]) -> f64 {
+    cubes
+        .iter()
+        .filter(|other| other.id != cube.id && other.top() <= cube.z + EPSILON)
+        .filter(|other| {
+            let (overlap_x, overlap_y) = cube.footprint_overlap(other);
+            overlap_x > 0.0 && overlap_y > 0.0
+        })
+        .map(|other| other.top())
+        .fold(0.0, f64::max)
+}
+
+// Height to carry a lifted cube at, so that it clears everything it's over.
+// Cubes stacked on top of the carried one don't count; they'll get knocked off.
+pub fn carry_height(cube: &Cube, cubes: &[Cube]) -> f64 {
+    let passengers = passengers(cube, cubes);
+
+    let highest = cubes
+        .iter()
+        .filter(|other| other.id != cube.id && !passengers.contains(&other.id))
+        .filter(|other| {
+            let (overlap_x, overlap_y) = cube.footprint_overlap(other);
+            overlap_x > 0.0 && overlap_y > 0.0
+        })
+        .map(|other| other.top())
+        .fold(0.0, f64::max);
+
+    highest + LIFT_HEIGHT
+}
+
+// Ids of every cube resting on this one, directly or further up the stack
+pub fn passengers(cube: &Cube, cubes: &[Cube]) -> Vec<usize> {
+    let mut supports = vec![*cube];
+    let mut passengers = vec![];
+
+    while let Some(support) = supports.pop() {
+        for other in cubes {
+            if other.id == cube.id || passengers.contains(&other.id) {
+                continue;
+            }
+
+            let (overlap_x, overlap_y) = support.footprint_overlap(other);
+
+            if overlap_x > 0.0 && overlap_y > 0.0 && (other.z - support.top()).abs() < EPSILON {
+                passengers.push(other.id);
+                supports.push(*other);
+            }
+        }
+    }
+
+    passengers
+}
+
+// Drops every cube which isn't resting on something, except the ones being held.
+// Returns whether anything is still in the air.
+pub fn step(cubes: &mut [Cube], held: &[usize], dt: f64) -> bool {
+    let mut falling = false;
+
+    // Settle from the bottom up, so cubes land on whatever already came to rest under them
+    let mut order: Vec<usize> = (0..cubes.len()).collect();
+    order.sort_by(|a, b| cubes[*a].z.total_cmp(&cubes[*b].z));
+
+    for i in order {
+        if held.contains(&cubes[i].id) {
+            continue;
+        }
+
+        let support = support_height(&cubes[i], cubes);
+        let cube = &mut cubes[i];
+
+        if cube.z <= support + EPSILON {
+            cube.z = support;
+            cube.velocity_z = 0.0;
+            continue;
+        }
+
+        cube.velocity_z -= GRAVITY * dt;
+        cube.z += cube.velocity_z * dt;
+
+        if cube.z <= support {
+            // Landed
+            cube.z = support;
+            cube.velocity_z = 0.0;
+        }
+
+        falling = true;
+    }
+
+    falling
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(id: usize, x: f64, y: f64, z: f64, size: f64) -> Cube {
+        let mut cube = Cube::new('A', x, y, size);
+        cube.id = id;
+        cube.z = z;
+        cube
+    }
+
+    fn settle(cubes: &mut [Cube]) {
+        let mut frames = 0;
+        while step(cubes, &[], 16.0) {
+            frames += 1;
+            assert!(frames < 1000);
+        }
+    }
+
+    #[test]
+    fn test_falls_to_floor() {
+        let mut cubes = vec![cube(1, 0.0, 0.0, 250.0, 100.0)];
+        settle(&mut cubes);
+        assert_eq!(cubes[0].z, 0.0);
+        assert_eq!(cubes[0].velocity_z, 0.0);
+    }
+
+    #[test]
+    fn test_stacks_on_other_cubes() {
+        // A tower of three, dropped from different heights, partially overlapping
+        let mut cubes = vec![
+            cube(1, 0.0, 0.0, 0.0, 100.0),
+            cube(2, 30.0, 20.0, 400.0, 60.0),
+            cube(3, 50.0, 40.0, 200.0, 100.0),
+        ];
+        settle(&mut cubes);
+        assert_eq!(cubes[0].z, 0.0);
+        assert_eq!(cubes[2].z, 100.0);
+        assert_eq!(cubes[1].z, 200.0);
+
+        // Held cubes stay in the air
+        let mut cubes = vec![cube(1, 0.0, 0.0, 300.0, 100.0)];
+        assert!(!step(&mut cubes, &[1], 16.0));
+        assert_eq!(cubes[0].z, 300.0);
+    }
+
+    #[test]
+    fn test_carry_height() {
+        let cubes = vec![
+            cube(1, 0.0, 0.0, 0.0, 100.0),
+            cube(2, 50.0, 50.0, 100.0, 60.0),
+            cube(3, 300.0, 0.0, 0.0, 100.0),
+        ];
+        let carried = cube(4, 80.0, 80.0, 0.0, 60.0);
+        assert_eq!(carry_height(&carried, &cubes), 160.0 + LIFT_HEIGHT);
+
+        // Lifting the bottom of a tower goes only as high as what's beside it
+        let cubes = vec![
+            cube(1, 0.0, 0.0, 0.0, 100.0),
+            cube(2, 10.0, 10.0, 100.0, 60.0),
+            cube(3, 20.0, 20.0, 160.0, 60.0),
+            cube(4, 90.0, 0.0, 0.0, 40.0),
+        ];
+        assert_eq!(passengers(&cubes[0], &cubes), vec![2, 3]);
+        assert_eq!(carry_height(&cubes[0], &cubes), 40.0 + LIFT_HEIGHT);
+    }
+}
@@ -1,4 +1,12 @@
 use geo::{coord, Coord};
+use serde::Deserialize;
+
+use crate::camera::Camera;
+use crate::projection::Projection;
+use crate::shading::Shading;
+
+// Text lying flat is drawn this many iso units per pixel of font size
+const TEXT_SCALE: f64 = std::f64::consts::FRAC_1_SQRT_2;
 
 #[derive(Default)]
 pub struct Grid {
@@ -8,12 +16,20 @@ pub struct Grid {
     pub device_pixel_ratio: f64,
 
     pub light_source: LightSource,
+    pub camera: Camera,
+    pub projection: Projection,
+    pub shading: Shading,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct LightSource {
     pub x: f64,
     pub y: f64,
     pub z: f64,
+    // How big it is across, which softens the edges of floor shadows. 0 is a point.
+    #[serde(default)]
+    pub radius: f64,
 }
 
 impl Default for LightSource {
@@ -22,6 +38,7 @@ impl Default for LightSource {
             x: -300.25,
             y: 300.25,
             z: 600.0,
+            radius: 0.0,
         }
     }
 }
@@ -29,29 +46,232 @@ impl Default for LightSource {
 impl Grid {
     pub fn project(&self, x: f64, y: f64, z: f64) -> Coord {
         let (left_offset, top_offset) = self.offset();
+        let zoom = self.camera.zoom;
+        let (x, y) = self.turn(x, y);
+        let screen = self.projection.axes().apply(x, y, z);
+
+        coord! {
+            x: screen.x * zoom + left_offset,
+            y: screen.y * zoom + top_offset,
+        }
+    }
 
-        let x = x - y;
-        let y = ((x + y * 2.0) / 2.0) - z;
+    // A canvas transform which lays text flat at (x, y, z), on the floor or the top of a
+    // cube, reading along -y. It turns and zooms along with everything else.
+    pub fn flat_text_transform(&self, x: f64, y: f64, z: f64) -> [f64; 6] {
+        let origin = self.project(x, y, z);
+        let across = self.project(x, y - TEXT_SCALE, z);
+        let down = self.project(x + TEXT_SCALE, y, z);
 
-        coord! { x: x + left_offset, y: y + top_offset }
+        [
+            across.x - origin.x,
+            across.y - origin.y,
+            down.x - origin.x,
+            down.y - origin.y,
+            origin.x,
+            origin.y,
+        ]
     }
 
-    pub fn cartesian_to_iso(&self, x: f64, y: f64) -> (f64, f64) {
+    // The inverses of `project`. A screen point could be anywhere along a line of sight,
+    // so these find where that line crosses a given plane: horizontal at height z,
+    // or upright at a given x or y.
+
+    pub fn unproject_at_z(&self, screen: Coord, z: f64) -> Coord {
+        let (floor, toward_viewer) = self.line_of_sight(screen);
+
+        coord! { x: floor.x + toward_viewer.x * z, y: floor.y + toward_viewer.y * z }
+    }
+
+    // Returns (y, z). Comes out infinite when the line of sight runs along the plane,
+    // which only happens halfway through turning the view.
+    pub fn unproject_at_x(&self, screen: Coord, x: f64) -> (f64, f64) {
+        let (floor, toward_viewer) = self.line_of_sight(screen);
+        let z = (x - floor.x) / toward_viewer.x;
+
+        (floor.y + toward_viewer.y * z, z)
+    }
+
+    // Returns (x, z)
+    pub fn unproject_at_y(&self, screen: Coord, y: f64) -> (f64, f64) {
+        let (floor, toward_viewer) = self.line_of_sight(screen);
+        let z = (y - floor.y) / toward_viewer.y;
+
+        (floor.x + toward_viewer.x * z, z)
+    }
+
+    // The line of sight through a screen point, as where it meets the floor and which
+    // way it heads across the floor for every unit it rises towards the viewer
+    fn line_of_sight(&self, screen: Coord) -> (Coord, Coord) {
+        let (x, y) = self.unzoom(screen);
+        let floor = self
+            .projection
+            .axes()
+            .unapply_on_floor(coord! { x: x, y: y });
+        let (x, y) = self.unturn(floor.x, floor.y);
+
+        (coord! { x: x, y: y }, self.view_direction())
+    }
+
+    // Which way the viewer is, on the floor, for every unit up. Unturned and dimetric,
+    // they're off towards +x and +y; along a line of sight, whatever's further that way
+    // is in front.
+    pub fn view_direction(&self) -> Coord {
+        let (cos, sin) = self.camera.cos_sin();
+        let Coord { x, y } = self.projection.axes().view_direction();
+
+        coord! { x: x * cos + y * sin, y: -x * sin + y * cos }
+    }
+
+    // Turns a point on the floor around the pivot, to where it's seen from
+    fn turn(&self, x: f64, y: f64) -> (f64, f64) {
+        let (cos, sin) = self.camera.cos_sin();
+        let Coord { x: px, y: py } = self.camera.pivot;
+        let (dx, dy) = (x - px, y - py);
+
+        (px + dx * cos - dy * sin, py + dx * sin + dy * cos)
+    }
+
+    fn unturn(&self, x: f64, y: f64) -> (f64, f64) {
+        let (cos, sin) = self.camera.cos_sin();
+        let Coord { x: px, y: py } = self.camera.pivot;
+        let (dx, dy) = (x - px, y - py);
+
+        (px + dx * cos + dy * sin, py - dx * sin + dy * cos)
+    }
+
+    // Undoes the offset and zoom on a screen point, leaving it as it'd be projected
+    // with neither
+    fn unzoom(&self, screen: Coord) -> (f64, f64) {
         let (left_offset, top_offset) = self.offset();
 
-        let x = (x - left_offset) * self.device_pixel_ratio / 2.0;
-        let y = (y - top_offset) * self.device_pixel_ratio;
+        (
+            (screen.x - left_offset) / self.camera.zoom,
+            (screen.y - top_offset) / self.camera.zoom,
+        )
+    }
 
-        ((x + y), (y - x))
+    // Converts a position from a mouse or touch event (in CSS pixels) into canvas pixels
+    pub fn to_device(&self, posn: &Coord) -> Coord {
+        coord! { x: posn.x * self.device_pixel_ratio, y: posn.y * self.device_pixel_ratio }
+    }
+
+    // Where a pointer position (in CSS pixels) lands on the floor
+    pub fn cartesian_to_iso(&self, x: f64, y: f64) -> (f64, f64) {
+        let Coord { x, y } = self.unproject_at_z(self.to_device(&coord! { x: x, y: y }), 0.0);
+
+        (x, y)
     }
 
     fn offset(&self) -> (f64, f64) {
-        let left_offset = self.width / 2.0;
-        let top_offset = (self.height - 600.0) / 2.0;
+        let left_offset = self.width / 2.0 + self.camera.pan.x;
+        let top_offset = (self.height - 600.0) / 2.0 + self.camera.pan.y;
 
         (left_offset, top_offset)
     }
 
+    // Moves the view by (dx, dy) CSS pixels
+    pub fn pan_by(&mut self, dx: f64, dy: f64) {
+        self.camera.pan.x += dx * self.device_pixel_ratio;
+        self.camera.pan.y += dy * self.device_pixel_ratio;
+        self.clamp_camera();
+    }
+
+    // Zooms in (or out, for factors below 1) around a point on the screen (CSS pixels),
+    // keeping whatever's on the floor under that point right where it is
+    pub fn zoom_at(&mut self, factor: f64, position: Coord) {
+        let screen = self.to_device(&position);
+        let anchor = self.unproject_at_z(screen, 0.0);
+
+        let limits = self.camera.limits;
+        self.camera.zoom = (self.camera.zoom * factor).clamp(limits.min_zoom, limits.max_zoom);
+
+        let moved = self.project(anchor.x, anchor.y, 0.0);
+        self.camera.pan.x += screen.x - moved.x;
+        self.camera.pan.y += screen.y - moved.y;
+        self.clamp_camera();
+    }
+
+    // Starts the view turning by some quarter turns (clockwise on screen)
+    pub fn rotate_by(&mut self, quarter_turns: i32) {
+        self.camera.target_rotation += quarter_turns as f64;
+    }
+
+    // Eases the view along towards the angle it's turning to. Returns whether it moved.
+    pub fn step_rotation(&mut self, dt: f64) -> bool {
+        let turning = self.camera.step_rotation(dt);
+        if turning {
+            self.clamp_camera();
+        }
+
+        turning
+    }
+
+    pub fn reset_camera(&mut self) {
+        self.camera.pan = coord! { x: 0.0, y: 0.0 };
+        self.camera.zoom = 1.0;
+        self.camera.rotation = 0.0;
+        self.camera.target_rotation = 0.0;
+        self.clamp_camera();
+    }
+
+    // Zooms and pans so that all of `points` (iso units, as (x, y, z)) are on screen and
+    // centred, with `margin` device pixels to spare all round. This ignores the scene's
+    // camera limits, as it's for pictures rather than for looking around.
+    pub fn fit(&mut self, points: &[(f64, f64, f64)], margin: f64) {
+        if points.is_empty() {
+            return;
+        }
+
+        self.camera.zoom = 1.0;
+        self.camera.pan = coord! { x: 0.0, y: 0.0 };
+        let (left_offset, top_offset) = self.offset();
+
+        let (mut min, mut max) = (
+            coord! { x: f64::INFINITY, y: f64::INFINITY },
+            coord! { x: f64::NEG_INFINITY, y: f64::NEG_INFINITY },
+        );
+        for &(x, y, z) in points {
+            let screen = self.project(x, y, z);
+            min = coord! { x: min.x.min(screen.x), y: min.y.min(screen.y) };
+            max = coord! { x: max.x.max(screen.x), y: max.y.max(screen.y) };
+        }
+
+        let zoom = ((self.width - 2.0 * margin) / (max.x - min.x).max(1.0))
+            .min((self.height - 2.0 * margin) / (max.y - min.y).max(1.0));
+        if !(zoom.is_finite() && zoom > 0.0) {
+            return;
+        }
+
+        // Everything scales about the offsets, so the middle of it all ends up at
+        // offset + (middle - offset) * zoom, which is moved to the middle of the screen
+        let middle = (min + max) / 2.0;
+        self.camera.zoom = zoom;
+        self.camera.pan = coord! {
+            x: self.width / 2.0 - left_offset - (middle.x - left_offset) * zoom,
+            y: self.height / 2.0 - top_offset - (middle.y - top_offset) * zoom,
+        };
+    }
+
+    // Keeps the zoom within the scene's limits, and the middle of the screen over the
+    // part of the floor it's allowed to see
+    fn clamp_camera(&mut self) {
+        let limits = self.camera.limits;
+        self.camera.zoom = self.camera.zoom.clamp(limits.min_zoom, limits.max_zoom);
+
+        if let Some(bounds) = limits.bounds {
+            let middle = coord! { x: self.width / 2.0, y: self.height / 2.0 };
+            let looking_at = self.unproject_at_z(middle, 0.0);
+            let allowed = bounds.clamp(looking_at);
+
+            if allowed != looking_at {
+                let moved = self.project(allowed.x, allowed.y, 0.0);
+                self.camera.pan.x += middle.x - moved.x;
+                self.camera.pan.y += middle.y - moved.y;
+            }
+        }
+    }
+
     pub fn update_dimensions(&mut self, width: f64, height: f64, device_pixel_ratio: f64) -> bool {
         let width = width * device_pixel_ratio;
         let height = height * device_pixel_ratio;
@@ -61,7 +281,162 @@ impl Grid {
         self.width = width;
         self.height = height;
         self.device_pixel_ratio = device_pixel_ratio;
+        self.clamp_camera();
 
         changed
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Bounds;
+
+    #[test]
+    fn test_unproject() {
+        let mut grid = Grid::default();
+        grid.update_dimensions(1280.0, 800.0, 2.0);
+
+        let screen = grid.project(120.0, -35.0, 60.0);
+
+        assert_eq!(
+            grid.unproject_at_z(screen, 60.0),
+            coord! { x: 120.0, y: -35.0 }
+        );
+        assert_eq!(grid.unproject_at_x(screen, 120.0), (-35.0, 60.0));
+        assert_eq!(grid.unproject_at_y(screen, -35.0), (120.0, 60.0));
+    }
+
+    fn assert_close(a: Coord, b: Coord) {
+        assert!(
+            (a.x - b.x).abs() < 1e-9 && (a.y - b.y).abs() < 1e-9,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn test_camera() {
+        let mut grid = Grid::default();
+        grid.update_dimensions(1280.0, 800.0, 2.0);
+        grid.pan_by(30.0, -45.0);
+        grid.zoom_at(1.5, coord! { x: 200.0, y: 100.0 });
+
+        // Still exact inverses, whatever the camera's doing
+        let screen = grid.project(120.0, -35.0, 60.0);
+        assert_close(
+            grid.unproject_at_z(screen, 60.0),
+            coord! { x: 120.0, y: -35.0 },
+        );
+
+        let floor = grid.unproject_at_z(coord! { x: 300.0, y: 500.0 }, 0.0);
+        let (x, y) = grid.cartesian_to_iso(150.0, 250.0);
+        assert_close(coord! { x: x, y: y }, floor);
+
+        // Whatever's under the point being zoomed around stays put
+        let (x, y) = grid.cartesian_to_iso(400.0, 300.0);
+        grid.zoom_at(2.0, coord! { x: 400.0, y: 300.0 });
+        assert_eq!(grid.camera.zoom, 3.0);
+        let (after_x, after_y) = grid.cartesian_to_iso(400.0, 300.0);
+        assert_close(coord! { x: after_x, y: after_y }, coord! { x: x, y: y });
+
+        grid.zoom_at(100.0, coord! { x: 0.0, y: 0.0 });
+        assert_eq!(grid.camera.zoom, grid.camera.limits.max_zoom);
+
+        grid.reset_camera();
+        assert_eq!(grid.camera.zoom, 1.0);
+        assert_eq!(grid.camera.pan, coord! { x: 0.0, y: 0.0 });
+    }
+
+    #[test]
+    fn test_fit() {
+        let mut grid = Grid::default();
+        grid.update_dimensions(1200.0, 630.0, 1.0);
+        let points = [
+            (-500.0, -900.0, 0.0),
+            (400.0, 300.0, 0.0),
+            (0.0, 0.0, 300.0),
+        ];
+        grid.fit(&points, 20.0);
+
+        // Everything's on screen, and it touches the margin on at least one side
+        let screens: Vec<Coord> = points
+            .iter()
+            .map(|&(x, y, z)| grid.project(x, y, z))
+            .collect();
+        for screen in &screens {
+            assert!(
+                (20.0 - 1e-9..=1180.0 + 1e-9).contains(&screen.x),
+                "{:?}",
+                screen
+            );
+            assert!(
+                (20.0 - 1e-9..=610.0 + 1e-9).contains(&screen.y),
+                "{:?}",
+                screen
+            );
+        }
+        let touches = |value: f64, edge: f64| (value - edge).abs() < 1e-9;
+        assert!(screens.iter().any(|screen| touches(screen.x, 20.0)
+            || touches(screen.x, 1180.0)
+            || touches(screen.y, 20.0)
+            || touches(screen.y, 610.0)));
+    }
+
+    #[test]
+    fn test_projections() {
+        for projection in [
+            Projection::Isometric,
+            Projection::Military,
+            Projection::Cavalier,
+        ] {
+            let mut grid = Grid {
+                projection,
+                ..Grid::default()
+            };
+            grid.update_dimensions(1280.0, 800.0, 2.0);
+            grid.zoom_at(1.5, coord! { x: 200.0, y: 100.0 });
+            grid.camera.rotation = 0.3;
+
+            let screen = grid.project(120.0, -35.0, 60.0);
+            assert_close(
+                grid.unproject_at_z(screen, 60.0),
+                coord! { x: 120.0, y: -35.0 },
+            );
+            let (y, z) = grid.unproject_at_x(screen, 120.0);
+            assert_close(coord! { x: y, y: z }, coord! { x: -35.0, y: 60.0 });
+
+            // Flat text runs along -y, and down the page along +x
+            let [a, b, c, d, e, f] = grid.flat_text_transform(10.0, 20.0, 30.0);
+            let origin = grid.project(10.0, 20.0, 30.0);
+            assert_close(coord! { x: e, y: f }, origin);
+            let across = grid.project(10.0, 20.0 - TEXT_SCALE * 10.0, 30.0);
+            assert_close(coord! { x: e + a * 10.0, y: f + b * 10.0 }, across);
+            let down = grid.project(10.0 + TEXT_SCALE * 10.0, 20.0, 30.0);
+            assert_close(coord! { x: e + c * 10.0, y: f + d * 10.0 }, down);
+        }
+    }
+
+    #[test]
+    fn test_camera_bounds() {
+        let mut grid = Grid::default();
+        grid.camera.limits.bounds = Some(Bounds {
+            min_x: 0.0,
+            min_y: 0.0,
+            max_x: 500.0,
+            max_y: 500.0,
+        });
+        grid.update_dimensions(1280.0, 800.0, 1.0);
+
+        // Panning far away stops with the middle of the screen at the edge of the bounds
+        grid.pan_by(-10000.0, 0.0);
+        let middle = grid.unproject_at_z(coord! { x: 640.0, y: 400.0 }, 0.0);
+        assert!(
+            middle.x <= 500.0 + 1e-9 && middle.y >= -1e-9,
+            "{:?}",
+            middle
+        );
+        assert!((middle.x - 500.0).abs() < 1e-9 || middle.y.abs() < 1e-9);
+    }
+}
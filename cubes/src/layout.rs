@@ -0,0 +1,270 @@
+use geo::Coord;
+use serde::Deserialize;
+
+use crate::cube::Cube;
+
+// How to lay a block of text out as cubes. Distances are in iso units.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutOptions {
+    // Gap between neighbouring letters
+    pub spacing: f64,
+    // Gap between lines
+    pub line_spacing: f64,
+    // Lines longer than this wrap onto the next one, between words where possible
+    pub max_width: Option<f64>,
+    pub align: Align,
+    pub jitter: Option<Jitter>,
+}
+
+// Where shorter lines sit relative to the longest one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Start,
+    Center,
+    End,
+}
+
+// Nudges every cube a little off the grid, so it looks like the letters were
+// put down by hand. The same seed always gives the same layout.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Jitter {
+    pub amount: f64,
+    pub seed: u64,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            spacing: 10.0,
+            line_spacing: 20.0,
+            max_width: None,
+            align: Align::Start,
+            jitter: None,
+        }
+    }
+}
+
+// Spells out `text` in cubes of `cube_size`, with the first letter at `origin`.
+//
+// Text reads the way the hand-placed pages do: along each line the letters run towards
+// -y (down and to the left on screen), and each line sits further along +x than the
+// last. Spaces take up a letter's worth of room, and newlines always start a new line.
+pub fn layout_text(
+    text: &str,
+    origin: Coord,
+    cube_size: f64,
+    options: &LayoutOptions,
+) -> Vec<Cube> {
+    let pitch = cube_size + options.spacing;
+    let line_pitch = cube_size + options.line_spacing;
+
+    let max_letters = options
+        .max_width
+        .map(|max_width| (((max_width + options.spacing) / pitch).floor() as usize).max(1));
+
+    let lines: Vec<Vec<char>> = text
+        .split('\n')
+        .flat_map(|paragraph| wrap(paragraph, max_letters))
+        .collect();
+
+    let longest = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+
+    // Jitter never moves a cube more than half the gap around it, so neighbours can't
+    // end up overlapping
+    let mut rng = options.jitter.map(|jitter| Rng::new(jitter.seed));
+    let jitter_amount = options.jitter.map_or(0.0, |jitter| jitter.amount.max(0.0));
+    let jitter_along = jitter_amount.min(options.spacing / 2.0);
+    let jitter_across = jitter_amount.min(options.line_spacing / 2.0);
+
+    let mut cubes = vec![];
+
+    for (i, line) in lines.iter().enumerate() {
+        let slack = (longest - line.len()) as f64 * pitch;
+        let offset = match options.align {
+            Align::Start => 0.0,
+            Align::Center => slack / 2.0,
+            Align::End => slack,
+        };
+
+        for (j, character) in line.iter().enumerate() {
+            if character.is_whitespace() {
+                continue;
+            }
+
+            let mut x = origin.x + i as f64 * line_pitch;
+            let mut y = origin.y - j as f64 * pitch - offset;
+
+            if let Some(rng) = &mut rng {
+                x += rng.between(-jitter_across, jitter_across);
+                y += rng.between(-jitter_along, jitter_along);
+            }
+
+            cubes.push(Cube::new(*character, x, y, cube_size));
+        }
+    }
+
+    cubes
+}
+
+// Breaks one paragraph into lines of at most `max_letters`, between words where it
+// can. Words which don't fit on a line of their own get split.
+fn wrap(paragraph: &str, max_letters: Option<usize>) -> Vec<Vec<char>> {
+    let Some(max_letters) = max_letters else {
+        return vec![paragraph.chars().collect()];
+    };
+
+    let mut lines: Vec<Vec<char>> = vec![];
+    let mut line: Vec<char> = vec![];
+
+    for word in paragraph.split(' ').filter(|word| !word.is_empty()) {
+        let word: Vec<char> = word.chars().collect();
+
+        if !line.is_empty() && line.len() + 1 + word.len() <= max_letters {
+            line.push(' ');
+            line.extend(&word);
+            continue;
+        }
+
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+
+        let mut chunks = word.chunks(max_letters).peekable();
+        while let Some(chunk) = chunks.next() {
+            if chunks.peek().is_some() {
+                lines.push(chunk.to_vec());
+            } else {
+                line = chunk.to_vec();
+            }
+        }
+    }
+
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+// xorshift64*, which is plenty random enough for nudging letters around
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // The state can't be zero, and close seeds shouldn't give similar layouts
+        Rng((seed ^ 0x9e37_79b9_7f4a_7c15).max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn between(&mut self, min: f64, max: f64) -> f64 {
+        let unit = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        min + (max - min) * unit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver;
+    use geo::coord;
+
+    fn letters(cubes: &[Cube]) -> Vec<(char, f64, f64)> {
+        cubes
+            .iter()
+            .map(|cube| (cube.character(), cube.coord.x, cube.coord.y))
+            .collect()
+    }
+
+    #[test]
+    fn test_layout_lines() {
+        let origin = coord! { x: 0.0, y: 300.0 };
+        let cubes = layout_text("AB C\nDE", origin, 100.0, &LayoutOptions::default());
+
+        assert_eq!(
+            letters(&cubes),
+            vec![
+                ('A', 0.0, 300.0),
+                ('B', 0.0, 190.0),
+                ('C', 0.0, -30.0),
+                ('D', 120.0, 300.0),
+                ('E', 120.0, 190.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wrap_and_align() {
+        let options = LayoutOptions {
+            spacing: 0.0,
+            line_spacing: 0.0,
+            max_width: Some(500.0),
+            align: Align::End,
+            jitter: None,
+        };
+        let cubes = layout_text("SOFTWARE DEV", coord! { x: 0.0, y: 0.0 }, 100.0, &options);
+
+        // SOFTWARE is too long to fit, so it's split, and DEV goes on the next line.
+        // Every line ends at the same place.
+        assert_eq!(
+            wrap("SOFTWARE DEV", Some(5)),
+            vec![
+                "SOFTW".chars().collect::<Vec<_>>(),
+                "ARE".chars().collect(),
+                "DEV".chars().collect(),
+            ]
+        );
+        let last_y = |x: f64| {
+            cubes
+                .iter()
+                .filter(|cube| cube.coord.x == x)
+                .map(|cube| cube.coord.y)
+                .fold(f64::INFINITY, f64::min)
+        };
+        assert_eq!(last_y(0.0), -400.0);
+        assert_eq!(last_y(100.0), -400.0);
+        assert_eq!(last_y(200.0), -400.0);
+
+        assert_eq!(
+            wrap("A BC D", Some(4)),
+            vec![vec!['A', ' ', 'B', 'C'], vec!['D']]
+        );
+    }
+
+    #[test]
+    fn test_jitter() {
+        let options = LayoutOptions {
+            jitter: Some(Jitter {
+                amount: 50.0,
+                seed: 7,
+            }),
+            ..LayoutOptions::default()
+        };
+        let text = "SOFTWARE\nDEVELOPER";
+        let origin = coord! { x: 360.0, y: 600.0 };
+
+        let cubes = layout_text(text, origin, 60.0, &options);
+        assert_eq!(
+            letters(&cubes),
+            letters(&layout_text(text, origin, 60.0, &options))
+        );
+
+        let straight = layout_text(text, origin, 60.0, &LayoutOptions::default());
+        assert_ne!(letters(&cubes), letters(&straight));
+
+        for (cube, straight) in cubes.iter().zip(&straight) {
+            assert!((cube.coord.x - straight.coord.x).abs() <= 10.0);
+            assert!((cube.coord.y - straight.coord.y).abs() <= 5.0);
+        }
+        assert!(solver::overlapping_pairs(&cubes).is_empty());
+    }
+}
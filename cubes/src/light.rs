@@ -0,0 +1,157 @@
+use geo::Coord;
+use serde::Deserialize;
+use std::f64::consts::{FRAC_1_SQRT_2, PI};
+
+use crate::grid::{Grid, LightSource};
+use crate::render::{FillRule, Renderer};
+
+// How the light moves about
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LightMode {
+    // Stays put, unless it's dragged around by the sun drawn where it is
+    #[default]
+    Fixed,
+    // Hangs right above the pointer
+    Flashlight,
+    // Follows the local time: the sun crosses the sky during the day, and the moon at
+    // night
+    Day,
+}
+
+impl LightMode {
+    pub fn next(self) -> Self {
+        match self {
+            LightMode::Fixed => LightMode::Flashlight,
+            LightMode::Flashlight => LightMode::Day,
+            LightMode::Day => LightMode::Fixed,
+        }
+    }
+}
+
+// Size of the sun handle (CSS pixels)
+const HANDLE_RADIUS: f64 = 18.0;
+
+// The sun's path through the day, around the middle of the scene (iso units). It rises
+// low on the viewer's left, climbs to its highest at noon, and sets on their right.
+const DAY_DISTANCE: f64 = 900.0;
+const DAY_LOWEST: f64 = 150.0;
+const DAY_HIGHEST: f64 = 750.0;
+
+// Where the light is at `hours` (0 to 24, local time) in Day mode
+pub fn day_position(hours: f64, center: Coord) -> LightSource {
+    // 0 at sunrise or moonrise, and π at sunset or moonset
+    let angle = ((hours - 6.0) / 12.0).rem_euclid(1.0) * PI;
+
+    // From the viewer's left to their right, and a little behind the scene
+    let across = -angle.cos() * DAY_DISTANCE;
+    let behind = DAY_DISTANCE / 3.0;
+
+    LightSource {
+        x: center.x + (across - behind) * FRAC_1_SQRT_2,
+        y: center.y + (-across - behind) * FRAC_1_SQRT_2,
+        z: DAY_LOWEST + (DAY_HIGHEST - DAY_LOWEST) * angle.sin(),
+        radius: 0.0,
+    }
+}
+
+// Whether a pointer position (CSS pixels) is on the sun drawn where the light is
+pub fn handle_hit_test(posn: &Coord, grid: &Grid) -> bool {
+    let center = handle_center(grid);
+    let posn = grid.to_device(posn);
+
+    (posn.x - center.x).hypot(posn.y - center.y) <= HANDLE_RADIUS * grid.device_pixel_ratio
+}
+
+// Draws the sun up where the light is, with a line down to the floor beneath it
+pub fn draw_handle(renderer: &dyn Renderer, grid: &Grid, is_active: bool) {
+    let dpr = grid.device_pixel_ratio;
+    let light = &grid.light_source;
+    let center = handle_center(grid);
+    let floor = grid.project(light.x, light.y, 0.0);
+
+    renderer.begin_path();
+    renderer.move_to(center.x, center.y);
+    renderer.line_to(floor.x, floor.y);
+    renderer.set_line_width(dpr);
+    renderer.set_stroke_style("rgba(41, 66, 82, 0.3)");
+    renderer.stroke();
+
+    renderer.begin_path();
+    renderer.arc(
+        center.x,
+        center.y,
+        HANDLE_RADIUS * dpr,
+        0.0,
+        std::f64::consts::TAU,
+    );
+    renderer.set_fill_style("#ffd54a");
+    renderer.fill(FillRule::NonZero);
+    renderer.set_line_width(2.0 * dpr);
+    renderer.set_stroke_style(if is_active { "#0000ff" } else { "#e0a800" });
+    renderer.stroke();
+}
+
+fn handle_center(grid: &Grid) -> Coord {
+    let light = &grid.light_source;
+    grid.project(light.x, light.y, light.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_day_position() {
+        let center = Coord { x: 100.0, y: 200.0 };
+
+        let sunrise = day_position(6.0, center);
+        let noon = day_position(12.0, center);
+        let sunset = day_position(17.99, center);
+
+        // Low, then high, then low again
+        assert_eq!(sunrise.z, DAY_LOWEST);
+        assert!((noon.z - DAY_HIGHEST).abs() < 1e-9);
+        assert!(sunset.z < DAY_LOWEST + 10.0);
+
+        // From the viewer's left (-x, +y) round to their right (+x, -y)
+        assert!(sunrise.x < center.x && sunrise.y > center.y);
+        assert!(sunset.x > center.x && sunset.y < center.y);
+
+        // The moon takes the same path at night
+        let moonrise = day_position(18.0, center);
+        assert!((moonrise.x - sunrise.x).abs() < 1e-9 && moonrise.z == sunrise.z);
+        assert_eq!(day_position(0.0, center), day_position(12.0, center));
+    }
+
+    #[test]
+    fn test_handle_hit_test() {
+        let mut grid = Grid::default();
+        grid.update_dimensions(1000.0, 800.0, 2.0);
+
+        let sun = grid.project(
+            grid.light_source.x,
+            grid.light_source.y,
+            grid.light_source.z,
+        );
+        let sun = Coord {
+            x: sun.x / 2.0,
+            y: sun.y / 2.0,
+        };
+        assert!(handle_hit_test(&sun, &grid));
+        assert!(handle_hit_test(
+            &Coord {
+                x: sun.x + 15.0,
+                y: sun.y
+            },
+            &grid
+        ));
+        assert!(!handle_hit_test(
+            &Coord {
+                x: sun.x + 25.0,
+                y: sun.y
+            },
+            &grid
+        ));
+    }
+}
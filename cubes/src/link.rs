@@ -1,11 +1,11 @@
 use crate::grid::Grid;
-use geo::{coord, polygon, Contains, Coord};
-use wasm_bindgen::prelude::*;
+use crate::render::{Composite, Renderer, TextAlign, TextBaseline};
+use geo::{coord, Contains, Coord, Rect};
 
 pub struct Link {
     coord: Coord,
-    text: &'static str,
-    pub url: &'static str,
+    text: String,
+    pub url: String,
 
     width: f64,
 
@@ -13,18 +13,11 @@ pub struct Link {
 }
 
 const FONT_STYLE: &str = "48px sans-serif";
+// About how wide a letter is in that font
+const AVERAGE_CHAR_WIDTH: f64 = 26.0;
 
 impl Link {
-    pub fn new(
-        x: f64,
-        y: f64,
-        text: &'static str,
-        url: &'static str,
-        context: &web_sys::CanvasRenderingContext2d,
-    ) -> Self {
-        context.set_font(FONT_STYLE);
-        let width = context.measure_text(text).unwrap().width();
-
+    pub fn new(x: f64, y: f64, text: String, url: String, width: f64) -> Self {
         Self {
             coord: coord!(x: x, y: y),
             text,
@@ -33,45 +26,55 @@ impl Link {
             is_active: false,
         }
     }
-    pub fn draw(&self, context: &web_sys::CanvasRenderingContext2d, grid: &Grid) {
-        let Coord { x, y } = grid.project(self.coord.x, self.coord.y, 0.0);
 
-        let _ = context.translate(x, y);
-        let _ = context.scale(1.0, 0.5);
-        let _ = context.rotate(-std::f64::consts::PI / 4.0);
+    // How wide the text is on screen, which decides how much of the floor the link takes up
+    pub fn measure_width(text: &str, context: &web_sys::CanvasRenderingContext2d) -> f64 {
         context.set_font(FONT_STYLE);
-        context.set_text_align("left");
-        context.set_text_baseline("middle");
-        context.set_fill_style(&JsValue::from_str("#0000ff"));
-        let _ = context.set_global_composite_operation("multiply");
-        let _ = context.fill_text(self.text, 0.0, 0.0);
-        let _ = context.set_global_composite_operation("source-over");
+        context.measure_text(text).unwrap().width()
+    }
+
+    // Roughly how wide the text is, for when there's no canvas to measure it with
+    pub fn estimate_width(text: &str) -> f64 {
+        text.chars().count() as f64 * AVERAGE_CHAR_WIDTH
+    }
+
+    pub fn draw(&self, renderer: &dyn Renderer, grid: &Grid) {
+        renderer.set_transform(grid.flat_text_transform(self.coord.x, self.coord.y, 0.0));
+        renderer.set_font(FONT_STYLE);
+        renderer.set_text_align(TextAlign::Left);
+        renderer.set_text_baseline(TextBaseline::Middle);
+        renderer.set_fill_style("#0000ff");
+        renderer.set_composite(Composite::Multiply);
+        renderer.fill_text(&self.text, 0.0, 0.0);
+        renderer.set_composite(Composite::SourceOver);
 
         if self.is_active {
             let (underline_x, underline_y) = (0.0, 25.0);
             let (underline_x2, underline_y2) = (self.width, 25.0);
-            context.begin_path();
-            context.move_to(underline_x, underline_y);
-            context.line_to(underline_x2, underline_y2);
-            context.set_line_width(4.0);
-            context.set_stroke_style(&JsValue::from_str("#0000ff"));
-            context.stroke();
+            renderer.begin_path();
+            renderer.move_to(underline_x, underline_y);
+            renderer.line_to(underline_x2, underline_y2);
+            renderer.set_line_width(4.0);
+            renderer.set_stroke_style("#0000ff");
+            renderer.stroke();
         }
 
-        let _ = context.reset_transform();
+        renderer.reset_transform();
     }
 
+    // Links lie flat on the floor, so picking one is a matter of finding where the
+    // pointer lands on the floor. Cubes in front are picked before links are.
     pub fn hit_test(&self, posn: &Coord, grid: &Grid) -> bool {
-        let c1 = grid.project(self.coord.x - 20.0, self.coord.y + 10.0, 0.0);
-        let c2 = grid.project(self.coord.x - 20.0, self.coord.y - self.width * 0.75, 0.0);
-        let c3 = grid.project(self.coord.x + 20.0, self.coord.y - self.width * 0.75, 0.0);
-        let c4 = grid.project(self.coord.x + 20.0, self.coord.y + 10.0, 0.0);
+        let Coord { x, y } = grid.unproject_at_z(grid.to_device(posn), 0.0);
 
-        let edges = polygon![c1, c2, c3, c4];
+        self.area().contains(&coord! { x: x, y: y })
+    }
 
-        edges.contains(&Coord {
-            x: posn.x * grid.device_pixel_ratio,
-            y: posn.y * grid.device_pixel_ratio,
-        })
+    // About the part of the floor the text covers (iso units)
+    pub fn area(&self) -> Rect {
+        Rect::new(
+            coord! { x: self.coord.x - 20.0, y: self.coord.y - self.width * 0.75 },
+            coord! { x: self.coord.x + 20.0, y: self.coord.y + 10.0 },
+        )
     }
 }
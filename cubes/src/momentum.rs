@@ -0,0 +1,113 @@
+use geo::Coord;
+use std::collections::VecDeque;
+
+// How much of a sliding cube's velocity is lost per second, as a decay rate.
+// A cube released at speed v covers roughly v / friction before stopping.
+pub const DEFAULT_FRICTION: f64 = 5.0;
+
+// Below this speed (iso units per ms) a sliding cube is considered at rest.
+const REST_SPEED: f64 = 0.01;
+
+// Only pointer samples this recent (ms) count towards the release velocity,
+// so pausing before letting go doesn't fling the cube.
+const VELOCITY_WINDOW: f64 = 100.0;
+
+// Frames longer than this (ms) are clamped, so a cube doesn't teleport
+// after the tab was in the background.
+pub const MAX_FRAME_TIME: f64 = 50.0;
+
+#[derive(Clone, Copy)]
+struct Sample {
+    time: f64,
+    position: Coord,
+}
+
+// Keeps a short history of pointer positions (in iso space) while dragging,
+// to estimate how fast the pointer was moving when the cube was let go.
+#[derive(Default)]
+pub struct VelocityTracker {
+    samples: VecDeque<Sample>,
+}
+
+impl VelocityTracker {
+    pub fn push(&mut self, time: f64, position: Coord) {
+        self.samples.push_back(Sample { time, position });
+        self.prune(time);
+    }
+
+    // Velocity in iso units per ms as of `now`, based on samples within the window.
+    pub fn velocity(&mut self, now: f64) -> Coord {
+        self.prune(now);
+
+        match (self.samples.front(), self.samples.back()) {
+            (Some(first), Some(last)) if last.time > first.time => {
+                let dt = last.time - first.time;
+                Coord {
+                    x: (last.position.x - first.position.x) / dt,
+                    y: (last.position.y - first.position.y) / dt,
+                }
+            }
+            _ => Coord { x: 0.0, y: 0.0 },
+        }
+    }
+
+    fn prune(&mut self, now: f64) {
+        while let Some(sample) = self.samples.front() {
+            if now - sample.time > VELOCITY_WINDOW {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+// Slows a velocity down over `dt` ms. Returns zero once it drops below resting speed.
+pub fn apply_friction(velocity: Coord, friction: f64, dt: f64) -> Coord {
+    let decay = (-friction * dt / 1000.0).exp();
+    let velocity = Coord {
+        x: velocity.x * decay,
+        y: velocity.y * decay,
+    };
+
+    if is_at_rest(velocity) {
+        Coord { x: 0.0, y: 0.0 }
+    } else {
+        velocity
+    }
+}
+
+pub fn is_at_rest(velocity: Coord) -> bool {
+    velocity.x.hypot(velocity.y) < REST_SPEED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_velocity_tracker() {
+        let mut tracker = VelocityTracker::default();
+        tracker.push(0.0, Coord { x: 0.0, y: 0.0 });
+        tracker.push(16.0, Coord { x: 8.0, y: 0.0 });
+        tracker.push(32.0, Coord { x: 16.0, y: -16.0 });
+        assert_eq!(tracker.velocity(32.0), Coord { x: 0.5, y: -0.5 });
+
+        // Holding still before release means no momentum
+        assert_eq!(tracker.velocity(500.0), Coord { x: 0.0, y: 0.0 });
+    }
+
+    #[test]
+    fn test_friction_comes_to_rest() {
+        let mut velocity = Coord { x: 2.0, y: -1.0 };
+        let mut frames = 0;
+
+        while !is_at_rest(velocity) {
+            velocity = apply_friction(velocity, DEFAULT_FRICTION, 16.0);
+            frames += 1;
+            assert!(frames < 1000);
+        }
+
+        assert_eq!(velocity, Coord { x: 0.0, y: 0.0 });
+    }
+}
@@ -141,73 +141,185 @@ impl Movement {
                 Direction::UpLeft => {
                     if cx_delta > cy_delta {
                         // Subject is to our left; move it left
-                        subject.translated(-overlap_x, 0.0)
+                        self.push(subject, Axis::X)
                     } else if cx_delta < cy_delta {
                         // Subject is to our right; move it up
-                        subject.translated(0.0, -overlap_y)
+                        self.push(subject, Axis::Y)
                     } else {
                         // Head on!
-                        subject.translated(-overlap_x, -overlap_y)
+                        self.push(subject, Axis::Both)
                     }
                 }
 
                 Direction::DownRight => {
                     if cx_delta < cy_delta {
                         // Subject is to our left; move it down
-                        subject.translated(0.0, overlap_y)
+                        self.push(subject, Axis::Y)
                     } else if cx_delta > cy_delta {
                         // Subject is to our right; move it right
-                        subject.translated(overlap_x, 0.0)
+                        self.push(subject, Axis::X)
                     } else {
                         // Head on!
-                        subject.translated(overlap_x, overlap_y)
+                        self.push(subject, Axis::Both)
                     }
                 }
 
                 Direction::DownLeft => {
                     if cx_delta > cy_delta {
                         // Subject is above us; move it left
-                        subject.translated(-overlap_x, 0.0)
+                        self.push(subject, Axis::X)
                     } else if cx_delta < cy_delta {
                         // Subject is below us; move it down
-                        subject.translated(0.0, overlap_y)
+                        self.push(subject, Axis::Y)
                     } else {
                         // Head on!
-                        subject.translated(-overlap_x, overlap_y)
+                        self.push(subject, Axis::Both)
                     }
                 }
 
                 Direction::UpRight => {
                     if cx_delta < cy_delta {
                         // Subject is above us; move it up
-                        subject.translated(0.0, -overlap_y)
+                        self.push(subject, Axis::Y)
                     } else if cx_delta > cy_delta {
                         // Subject is below us; move it right
-                        subject.translated(overlap_x, 0.0)
+                        self.push(subject, Axis::X)
                     } else {
                         // Head on!
-                        subject.translated(overlap_x, -overlap_y)
+                        self.push(subject, Axis::Both)
                     }
                 }
 
-                Direction::Left => subject.translated(-overlap_x, 0.0),
-                Direction::Right => subject.translated(overlap_x, 0.0),
-                Direction::Up => subject.translated(0.0, -overlap_y),
-                Direction::Down => subject.translated(0.0, overlap_y),
+                Direction::Left | Direction::Right => self.push(subject, Axis::X),
+                Direction::Up | Direction::Down => self.push(subject, Axis::Y),
 
                 Direction::None => subject,
             };
 
             Some(Movement::new(subject, end))
-        } else if self.poly.intersects(&subject.bounds) {
+        } else if self.end.overlaps_vertically(&subject) && self.poly.intersects(&subject.bounds) {
             // The final position of the cube which moved does NOT overlap with subject cube,
             // but it did completely pass through the subject cube on its way there.
-            // Here we use the motion sweep polygon `poly` to determine what to do.
-            // TODO allowing pass through for now
+            // Here we find the moment during the sweep when the two cubes first touched,
+            // and push the subject out ahead of us along the axis we hit it on.
+            let (_, axis) = self.time_of_impact(&subject)?;
+
+            Some(Movement::new(subject, self.push(subject, axis)))
+        } else {
             None
+        }
+    }
+
+    // The same movement, cut short where it would first run into `obstacle`, for when
+    // the obstacle isn't allowed to be pushed. Whatever's left along the other axis
+    // still happens, so the cube slides along the obstacle rather than sticking to it.
+    pub fn clipped(&self, obstacle: &Cube) -> Movement {
+        if !self.end.overlaps_vertically(obstacle) {
+            return Movement::new(self.start, self.end);
+        }
+
+        match self.time_of_impact(obstacle) {
+            Some((t, Axis::X)) => Movement::new_from_delta(self.start, self.dx * t, self.dy),
+            Some((t, Axis::Y)) => Movement::new_from_delta(self.start, self.dx, self.dy * t),
+            Some((t, Axis::Both)) => Movement::new_from_delta(self.start, self.dx * t, self.dy * t),
+            None => Movement::new(self.start, self.end),
+        }
+    }
+
+    // Continuous collision detection between the moving cube and a stationary subject,
+    // using the separating axis (slab) method on both axes. Returns the fraction of the
+    // movement (0.0 to 1.0) at which the two first touch, and which axis they touched on.
+    //
+    // Cubes which already overlap at the start of the movement are not considered hits;
+    // those are resolved by the overlap logic in `impact`.
+    fn time_of_impact(&self, subject: &Cube) -> Option<(f64, Axis)> {
+        let start_min = self.start.bounds.min();
+        let start_max = self.start.bounds.max();
+        let subject_min = subject.bounds.min();
+        let subject_max = subject.bounds.max();
+
+        let (x_enter, x_exit) = slab(
+            start_min.x,
+            start_max.x,
+            subject_min.x,
+            subject_max.x,
+            self.dx,
+        )?;
+        let (y_enter, y_exit) = slab(
+            start_min.y,
+            start_max.y,
+            subject_min.y,
+            subject_max.y,
+            self.dy,
+        )?;
+
+        let enter = x_enter.max(y_enter);
+        let exit = x_exit.min(y_exit);
+
+        if enter >= exit || !(0.0..=1.0).contains(&enter) {
+            return None;
+        }
+
+        let axis = match x_enter.partial_cmp(&y_enter) {
+            Some(Ordering::Greater) => Axis::X,
+            Some(Ordering::Less) => Axis::Y,
+            _ => Axis::Both,
+        };
+
+        Some((enter, axis))
+    }
+
+    // Moves the subject in the direction of this movement along the given axis,
+    // just far enough that it no longer overlaps our final position.
+    //
+    // This is the overlap when we only clipped the subject's near edge, but it also
+    // covers the case where we moved far enough in one frame to end up past its center.
+    fn push(&self, subject: Cube, axis: Axis) -> Cube {
+        let end_min = self.end.bounds.min();
+        let end_max = self.end.bounds.max();
+        let subject_min = subject.bounds.min();
+        let subject_max = subject.bounds.max();
+
+        let push_x = match self.dx.partial_cmp(&0.0) {
+            Some(Ordering::Greater) => end_max.x - subject_min.x,
+            Some(Ordering::Less) => end_min.x - subject_max.x,
+            _ => 0.0,
+        };
+
+        let push_y = match self.dy.partial_cmp(&0.0) {
+            Some(Ordering::Greater) => end_max.y - subject_min.y,
+            Some(Ordering::Less) => end_min.y - subject_max.y,
+            _ => 0.0,
+        };
+
+        match axis {
+            Axis::X => subject.translated(push_x, 0.0),
+            Axis::Y => subject.translated(0.0, push_y),
+            Axis::Both => subject.translated(push_x, push_y),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Axis {
+    X,
+    Y,
+    Both,
+}
+
+// Entry and exit times of a moving 1D span [min, max] (moving by delta over the movement)
+// against a stationary span [other_min, other_max]. None if they never share this axis.
+fn slab(min: f64, max: f64, other_min: f64, other_max: f64, delta: f64) -> Option<(f64, f64)> {
+    if delta == 0.0 {
+        if max > other_min && min < other_max {
+            Some((f64::NEG_INFINITY, f64::INFINITY))
         } else {
             None
         }
+    } else if delta > 0.0 {
+        Some(((other_min - max) / delta, (other_max - min) / delta))
+    } else {
+        Some(((other_max - min) / delta, (other_min - max) / delta))
     }
 }
 
@@ -245,13 +357,14 @@ mod tests {
         assert_eq!(impact.dx, 0.0);
         assert_eq!(impact.dy, 90.0);
 
-        // Moving x when that's the largest overlap, because of a huge y delta
+        // Moving x when that's the largest overlap, because of a huge x delta.
+        // We end up past the subject's center, so it gets pushed all the way clear of us.
         let start = Cube::new('A', 0.0, 0.0, 100.0);
         let end = Cube::new('A', 140.0, 20.0, 100.0);
         let movement = Movement::new(start, end);
         let subject = Cube::new('B', 110.0, 10.0, 100.0);
         let impact = movement.impact(subject).unwrap();
-        assert_eq!(impact.dx, 70.0);
+        assert_eq!(impact.dx, 130.0);
         assert_eq!(impact.dy, 0.0);
 
         // Moving x and y when the movement and overlap are truly equal
@@ -276,6 +389,68 @@ mod tests {
         assert_eq!(impact.dy, -10.0);
     }
 
+    #[test]
+    fn test_cube_movement_pass_through() {
+        // Moving so fast we'd end up entirely on the other side of the subject
+        let start = Cube::new('A', 0.0, 0.0, 100.0);
+        let end = Cube::new('A', 400.0, 0.0, 100.0);
+        let movement = Movement::new(start, end);
+        let subject = Cube::new('B', 200.0, 20.0, 100.0);
+        assert_eq!(movement.end.overlap(&subject), (0.0, 80.0));
+        let impact = movement.impact(subject).unwrap();
+        assert_eq!(impact.dx, 300.0);
+        assert_eq!(impact.dy, 0.0);
+
+        // Same thing diagonally; we first touch the subject's top edge
+        let start = Cube::new('A', 0.0, 0.0, 100.0);
+        let end = Cube::new('A', 300.0, 400.0, 100.0);
+        let movement = Movement::new(start, end);
+        let subject = Cube::new('B', 120.0, 200.0, 100.0);
+        let (t, axis) = movement.time_of_impact(&subject).unwrap();
+        assert_eq!(t, 0.25);
+        assert_eq!(axis, Axis::Y);
+        let impact = movement.impact(subject).unwrap();
+        assert_eq!(impact.dx, 0.0);
+        assert_eq!(impact.dy, 300.0);
+        let (overlap_x, overlap_y) = impact.end.overlap(&movement.end);
+        assert!(overlap_x == 0.0 || overlap_y == 0.0);
+
+        // A sweep that passes right by the subject without touching it
+        let start = Cube::new('A', 0.0, 0.0, 100.0);
+        let end = Cube::new('A', 400.0, 0.0, 100.0);
+        let movement = Movement::new(start, end);
+        let subject = Cube::new('B', 200.0, 100.0, 100.0);
+        assert!(movement.impact(subject).is_none());
+
+        // Sailing right over the top of the subject
+        let mut start = Cube::new('A', 0.0, 0.0, 100.0);
+        start.z = 120.0;
+        let movement = Movement::new(start, start.translated(400.0, 0.0));
+        let subject = Cube::new('B', 200.0, 20.0, 100.0);
+        assert!(movement.impact(subject).is_none());
+        let subject = Cube::new('B', 380.0, 20.0, 100.0);
+        assert!(movement.impact(subject).is_none());
+    }
+
+    #[test]
+    fn test_cube_movement_clipped() {
+        // Heading down and to the right into a cube that won't budge. We touch its left
+        // edge halfway there, then slide along it.
+        let start = Cube::new('A', 0.0, 0.0, 100.0);
+        let movement = Movement::new_from_delta(start, 100.0, 20.0);
+        let obstacle = Cube::new('B', 150.0, 50.0, 100.0);
+        let clipped = movement.clipped(&obstacle);
+        assert_eq!(clipped.dx, 50.0);
+        assert_eq!(clipped.dy, 20.0);
+        assert_eq!(clipped.end.overlap(&obstacle).0, 0.0);
+
+        // Nothing in the way
+        let obstacle = Cube::new('B', 150.0, 200.0, 100.0);
+        let clipped = movement.clipped(&obstacle);
+        assert_eq!((clipped.dx, clipped.dy), (100.0, 20.0));
+    }
+
+    #[test]
     fn test_cube_movement_directions() {
         // DownLeft
         let start = Cube::new('A', 0.0, 0.0, 100.0);
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cube::Cube;
+use crate::scene::{self, Scene};
+
+// Where a visitor left the cubes on a page, so they're still there next time.
+// This only deals in strings; CubeGrid is what reads and writes localStorage.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedLayout {
+    // Hash of the scene file the layout was made from. If the scene has changed
+    // since, the cubes in it probably have too, so the save is thrown out.
+    pub scene_hash: u64,
+    pub cubes: Vec<SavedCube>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SavedCube {
+    pub id: usize,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+// How long to wait after the cubes settle before saving, in case they're picked
+// right back up (ms)
+pub const SAVE_DELAY: f64 = 500.0;
+
+// Saves are kept per scene rather than per URL, so "/projects/" shares "/projects"'s
+// and every path without a scene of its own shares the 404 page's
+pub fn storage_key(path: &str) -> String {
+    if Scene::exists(path) {
+        format!("cubes:{}", scene::normalize(path))
+    } else {
+        "cubes:404".to_string()
+    }
+}
+
+// FNV-1a, which is stable between builds (unlike std's hasher), so saves made by
+// an older version of the site still match if the scene didn't change
+pub fn scene_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+impl SavedLayout {
+    pub fn new(scene_hash: u64, cubes: &[Cube]) -> Self {
+        let mut cubes: Vec<SavedCube> = cubes
+            .iter()
+            .map(|cube| SavedCube {
+                id: cube.id,
+                x: cube.coord.x,
+                y: cube.coord.y,
+                z: cube.z,
+            })
+            .collect();
+        cubes.sort_by_key(|cube| cube.id);
+
+        Self { scene_hash, cubes }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
+
+    // Reads a save back, if it's for this version of the scene and makes sense
+    pub fn from_json(json: &str, scene_hash: u64) -> Option<Self> {
+        let layout: SavedLayout = serde_json::from_str(json).ok()?;
+
+        let is_valid = layout.scene_hash == scene_hash
+            && layout
+                .cubes
+                .iter()
+                .all(|cube| cube.x.is_finite() && cube.y.is_finite() && cube.z >= 0.0);
+
+        if is_valid {
+            Some(layout)
+        } else {
+            None
+        }
+    }
+
+    // Moves each cube to where it was saved. Gives up without touching anything unless
+    // every cube has exactly one saved position.
+    pub fn apply(&self, cubes: &mut [Cube]) -> bool {
+        let positions: Vec<Option<&SavedCube>> = cubes
+            .iter()
+            .map(|cube| self.cubes.iter().find(|saved| saved.id == cube.id))
+            .collect();
+
+        if self.cubes.len() != cubes.len() || positions.contains(&None) {
+            return false;
+        }
+
+        for (cube, saved) in cubes.iter_mut().zip(positions) {
+            let saved = saved.unwrap();
+            cube.translate(saved.x - cube.coord.x, saved.y - cube.coord.y);
+            cube.z = saved.z;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(id: usize, x: f64, y: f64, z: f64) -> Cube {
+        let mut cube = Cube::new('A', x, y, 100.0);
+        cube.id = id;
+        cube.z = z;
+        cube
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let moved = vec![cube(2, 300.0, -20.0, 100.0), cube(1, 300.0, 0.0, 0.0)];
+        let json = SavedLayout::new(42, &moved).to_json();
+
+        let mut cubes = vec![cube(1, 0.0, 0.0, 0.0), cube(2, 200.0, 0.0, 0.0)];
+        let layout = SavedLayout::from_json(&json, 42).unwrap();
+        assert!(layout.apply(&mut cubes));
+
+        assert_eq!(
+            (cubes[0].coord.x, cubes[0].coord.y, cubes[0].z),
+            (300.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            (cubes[1].coord.x, cubes[1].coord.y, cubes[1].z),
+            (300.0, -20.0, 100.0)
+        );
+        assert_eq!(cubes[1].bounds.min().x, 300.0);
+    }
+
+    #[test]
+    fn test_stale_saves() {
+        let json = SavedLayout::new(42, &[cube(1, 10.0, 10.0, 0.0)]).to_json();
+
+        // The scene changed
+        assert_eq!(SavedLayout::from_json(&json, 43), None);
+        assert_eq!(SavedLayout::from_json("{ nonsense", 42), None);
+
+        // Different cubes than were saved
+        let layout = SavedLayout::from_json(&json, 42).unwrap();
+        let mut cubes = vec![cube(1, 0.0, 0.0, 0.0), cube(2, 200.0, 0.0, 0.0)];
+        assert!(!layout.apply(&mut cubes));
+        assert_eq!(cubes[0].coord.x, 0.0);
+
+        assert_ne!(scene_hash("{}"), scene_hash("{ }"));
+    }
+
+    #[test]
+    fn test_storage_key() {
+        assert_eq!(storage_key("/"), "cubes:/");
+        assert_eq!(storage_key("/projects/"), storage_key("/projects"));
+        assert_eq!(storage_key("/nowhere"), storage_key("/somewhere/else"));
+        assert_ne!(storage_key("/nowhere"), storage_key("/"));
+    }
+}
@@ -0,0 +1,101 @@
+use geo::Coord;
+
+use crate::cube::{Cube, Hit};
+use crate::grid::Grid;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pick {
+    pub id: usize,
+    pub hit: Hit,
+}
+
+// Finds the cube under a pointer position (in CSS pixels), along with the face and
+// world point that was hit.
+//
+// `cubes` must be in the order `sort_cubes` leaves them in, front-most first, so the
+// first cube we hit is the one drawn on top at that point.
+pub fn pick(cubes: &[Cube], grid: &Grid, posn: &Coord) -> Option<Pick> {
+    let screen = grid.to_device(posn);
+
+    cubes
+        .iter()
+        .find_map(|cube| cube.pick(screen, grid).map(|hit| Pick { id: cube.id, hit }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cube::Face;
+
+    fn grid() -> Grid {
+        let mut grid = Grid::default();
+        grid.update_dimensions(1000.0, 800.0, 1.0);
+        grid
+    }
+
+    fn cube(id: usize, x: f64, y: f64, z: f64, size: f64) -> Cube {
+        let mut cube = Cube::new('A', x, y, size);
+        cube.id = id;
+        cube.z = z;
+        cube
+    }
+
+    #[test]
+    fn test_pick_faces() {
+        let grid = grid();
+        let cubes = vec![cube(1, 0.0, 0.0, 40.0, 100.0)];
+
+        let posn = grid.project(30.0, 60.0, 140.0);
+        let picked = pick(&cubes, &grid, &posn).unwrap();
+        assert_eq!(picked.id, 1);
+        assert_eq!(picked.hit.face, Face::Top);
+        assert_eq!(
+            (picked.hit.x, picked.hit.y, picked.hit.z),
+            (30.0, 60.0, 140.0)
+        );
+
+        let posn = grid.project(30.0, 100.0, 90.0);
+        let picked = pick(&cubes, &grid, &posn).unwrap();
+        assert_eq!(picked.hit.face, Face::MaxY);
+        assert_eq!(
+            (picked.hit.x, picked.hit.y, picked.hit.z),
+            (30.0, 100.0, 90.0)
+        );
+
+        let posn = grid.project(100.0, 20.0, 50.0);
+        let picked = pick(&cubes, &grid, &posn).unwrap();
+        assert_eq!(picked.hit.face, Face::MaxX);
+        assert_eq!(
+            (picked.hit.x, picked.hit.y, picked.hit.z),
+            (100.0, 20.0, 50.0)
+        );
+
+        // Just under the bottom edge of the cube, which sits above the floor
+        let posn = grid.project(50.0, 100.0, 20.0);
+        assert!(pick(&cubes, &grid, &posn).is_none());
+    }
+
+    #[test]
+    fn test_pick_occluded() {
+        let grid = grid();
+
+        // A small cube right in front of a big one, hiding part of its left face.
+        // Front-most first, as sort_cubes leaves them.
+        let cubes = vec![
+            cube(2, 40.0, 110.0, 0.0, 60.0),
+            cube(1, 0.0, 0.0, 0.0, 100.0),
+        ];
+
+        // This point is on the big cube's left face, but the small cube is in the way
+        let posn = grid.project(60.0, 100.0, 30.0);
+        let picked = pick(&cubes, &grid, &posn).unwrap();
+        assert_eq!(picked.id, 2);
+        assert_eq!(picked.hit.face, Face::Top);
+
+        // Higher up, the big cube is visible above the small one
+        let posn = grid.project(60.0, 100.0, 90.0);
+        let picked = pick(&cubes, &grid, &posn).unwrap();
+        assert_eq!(picked.id, 1);
+        assert_eq!(picked.hit.face, Face::MaxY);
+    }
+}
@@ -0,0 +1,281 @@
+use geo::Coord;
+
+use crate::momentum::VelocityTracker;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PointerKind {
+    Mouse,
+    Pen,
+    Touch,
+}
+
+impl PointerKind {
+    // Whether it can point at things without pressing, so hovering means something
+    pub fn can_hover(self) -> bool {
+        self != PointerKind::Touch
+    }
+}
+
+// A mouse, pen or finger moving, pressing or letting go. Positions are in CSS pixels
+// and times are in ms, as the browser reports them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointerInput {
+    pub id: i32,
+    pub kind: PointerKind,
+    pub is_pressed: bool,
+    pub position: Coord,
+    pub time: f64,
+    pub shift_key: bool,
+}
+
+// Which cube a pointer grabbed, and where on it. The cube is kept with that point
+// right under the pointer for as long as it's held.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grab {
+    pub cube: usize,
+    // From the cube's corner to the point that was grabbed, on the floor (iso units)
+    pub offset: Coord,
+    // How far above the bottom of the cube that point is
+    pub height: f64,
+}
+
+// A mouse button, pen or finger that's pressed down. Each one drags its own cube, if
+// it came down on one.
+pub struct Pointer {
+    pub id: i32,
+    pub kind: PointerKind,
+
+    // Where it is now (CSS pixels)
+    pub current_position: Coord,
+
+    // Just came down, and nobody's looked at what it landed on yet
+    pub is_new: bool,
+    pub grab: Option<Grab>,
+    // Holding the sun, this far from it (iso units, at the light's height)
+    pub light_offset: Option<Coord>,
+    // Shift was held when it came down, so its cube gets picked up
+    pub shift_key: bool,
+    // Came down on empty space while another pointer was on empty space too. Those
+    // are saved for panning and zooming, so they never grab cubes or follow links.
+    pub is_gesture: bool,
+    // The browser took it away (or the window lost focus) rather than it being let go
+    pub is_cancelled: bool,
+
+    // Recent positions in iso space, for flinging
+    pub velocity: VelocityTracker,
+    pub last_event_time: f64,
+}
+
+// Two pointers panning and zooming together, as of one moment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gesture {
+    pub ids: (i32, i32),
+    // Halfway between them (CSS pixels)
+    pub midpoint: Coord,
+    pub distance: f64,
+}
+
+// Every pointer that's pressed, and the ones let go since World last looked
+#[derive(Default)]
+pub struct Pointers {
+    pub down: Vec<Pointer>,
+    pub lifted: Vec<Pointer>,
+}
+
+impl Pointers {
+    // Catches up with one pointer event. Only whether it's pressed matters, not which
+    // event it was, so a press or release the browser never told us about can't leave
+    // a drag stuck. `iso` is the position in iso space, for the velocity tracker.
+    pub fn update(&mut self, input: &PointerInput, iso: Coord) {
+        let index = self.down.iter().position(|pointer| pointer.id == input.id);
+
+        let index = match (index, input.is_pressed) {
+            (Some(index), _) => index,
+            (None, true) => {
+                self.down.push(Pointer {
+                    id: input.id,
+                    kind: input.kind,
+                    current_position: input.position,
+                    is_new: true,
+                    grab: None,
+                    light_offset: None,
+                    shift_key: input.shift_key,
+                    is_gesture: false,
+                    is_cancelled: false,
+                    velocity: VelocityTracker::default(),
+                    last_event_time: input.time,
+                });
+                self.down.len() - 1
+            }
+            // Just hovering
+            (None, false) => return,
+        };
+
+        let pointer = &mut self.down[index];
+        pointer.current_position = input.position;
+        pointer.last_event_time = input.time;
+        pointer.velocity.push(input.time, iso);
+
+        if !input.is_pressed {
+            let pointer = self.down.remove(index);
+            self.lifted.push(pointer);
+        }
+    }
+
+    pub fn cancel(&mut self, id: i32) {
+        if let Some(index) = self.down.iter().position(|pointer| pointer.id == id) {
+            let mut pointer = self.down.remove(index);
+            pointer.is_cancelled = true;
+            self.lifted.push(pointer);
+        }
+    }
+
+    pub fn cancel_all(&mut self) {
+        for mut pointer in std::mem::take(&mut self.down) {
+            pointer.is_cancelled = true;
+            self.lifted.push(pointer);
+        }
+    }
+
+    // Ids of the cubes being dragged
+    pub fn held_cubes(&self) -> Vec<usize> {
+        self.down
+            .iter()
+            .filter_map(|pointer| pointer.grab)
+            .map(|grab| grab.cube)
+            .collect()
+    }
+
+    // Ids of the cubes being dragged with shift held
+    pub fn lifted_cubes(&self) -> Vec<usize> {
+        self.down
+            .iter()
+            .filter(|pointer| pointer.shift_key)
+            .filter_map(|pointer| pointer.grab)
+            .map(|grab| grab.cube)
+            .collect()
+    }
+
+    // Once two pointers are down on empty space, they're a gesture for as long as
+    // they stay down, even if the other one lets go first
+    pub fn mark_gestures(&mut self) {
+        let mut empty = self
+            .down
+            .iter_mut()
+            .filter(|pointer| {
+                !pointer.is_new && pointer.grab.is_none() && pointer.light_offset.is_none()
+            })
+            .collect::<Vec<_>>();
+
+        if empty.len() >= 2 {
+            for pointer in &mut empty {
+                pointer.is_gesture = true;
+            }
+        }
+    }
+
+    // The first two gesture pointers still down, if there are two
+    pub fn gesture(&self) -> Option<Gesture> {
+        let mut fingers = self.down.iter().filter(|pointer| pointer.is_gesture);
+        let (a, b) = (fingers.next()?, fingers.next()?);
+
+        let (a_pos, b_pos) = (a.current_position, b.current_position);
+
+        Some(Gesture {
+            ids: (a.id, b.id),
+            midpoint: Coord {
+                x: (a_pos.x + b_pos.x) / 2.0,
+                y: (a_pos.y + b_pos.y) / 2.0,
+            },
+            distance: (a_pos.x - b_pos.x).hypot(a_pos.y - b_pos.y),
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.down.clear();
+        self.lifted.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::coord;
+
+    fn update(pointers: &mut Pointers, id: i32, is_pressed: bool, x: f64, time: f64) {
+        let input = PointerInput {
+            id,
+            kind: PointerKind::Touch,
+            is_pressed,
+            position: coord! { x: x, y: 10.0 },
+            time,
+            shift_key: false,
+        };
+        pointers.update(&input, input.position);
+    }
+
+    #[test]
+    fn test_update() {
+        let mut pointers = Pointers::default();
+
+        // Moving without pressing is just hovering
+        update(&mut pointers, 1, false, 0.0, 0.0);
+        assert!(pointers.down.is_empty());
+        assert!(pointers.lifted.is_empty());
+
+        update(&mut pointers, 3, true, 10.0, 0.0);
+        update(&mut pointers, 7, true, 50.0, 16.0);
+        assert_eq!(pointers.down.len(), 2);
+        assert!(pointers.down.iter().all(|pointer| pointer.is_new));
+
+        // The second one moves, then the first lets go
+        update(&mut pointers, 7, true, 60.0, 32.0);
+        update(&mut pointers, 3, false, 10.0, 40.0);
+        assert_eq!(pointers.down.len(), 1);
+        assert_eq!(pointers.down[0].id, 7);
+        assert_eq!(pointers.down[0].current_position.x, 60.0);
+        assert_eq!(pointers.lifted.len(), 1);
+        assert_eq!(pointers.lifted[0].id, 3);
+        assert!(!pointers.lifted[0].is_cancelled);
+
+        pointers.cancel(7);
+        pointers.cancel(7);
+        assert!(pointers.down.is_empty());
+        assert_eq!(pointers.lifted.len(), 2);
+        assert!(pointers.lifted[1].is_cancelled);
+    }
+
+    #[test]
+    fn test_gestures() {
+        let mut pointers = Pointers::default();
+        update(&mut pointers, 1, true, 0.0, 0.0);
+        update(&mut pointers, 2, true, 0.0, 0.0);
+        pointers.down[0].is_new = false;
+        pointers.down[0].grab = Some(Grab {
+            cube: 4,
+            offset: coord! { x: 0.0, y: 0.0 },
+            height: 0.0,
+        });
+        pointers.down[1].is_new = false;
+
+        // One on a cube and one on empty space is just dragging
+        pointers.mark_gestures();
+        assert!(pointers.down.iter().all(|pointer| !pointer.is_gesture));
+        assert_eq!(pointers.held_cubes(), vec![4]);
+
+        update(&mut pointers, 3, true, 0.0, 16.0);
+        pointers.down[2].is_new = false;
+        pointers.mark_gestures();
+        assert!(!pointers.down[0].is_gesture);
+        assert!(pointers.down[1].is_gesture && pointers.down[2].is_gesture);
+
+        update(&mut pointers, 3, true, 40.0, 32.0);
+        let gesture = pointers.gesture().unwrap();
+        assert_eq!(gesture.ids, (2, 3));
+        assert_eq!(gesture.midpoint, coord! { x: 20.0, y: 10.0 });
+        assert_eq!(gesture.distance, 40.0);
+
+        update(&mut pointers, 2, false, 0.0, 48.0);
+        assert_eq!(pointers.gesture(), None);
+    }
+}
@@ -0,0 +1,197 @@
+use geo::Coord;
+use serde::Deserialize;
+
+// How the world is flattened onto the screen. Every kind is a parallel projection, given
+// by where one unit along each world axis ends up on screen (canvas pixels, before
+// zooming). The viewer is always above the floor, off towards wherever lines of sight
+// come from.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+pub enum Projection {
+    // True isometric: all three axes 120° apart and equally shortened
+    Isometric,
+    // The 2:1 "pixel art" look, where floor lines climb one pixel for every two across
+    #[default]
+    Dimetric,
+    // The floor plan as it is, just turned 45°, with everything standing straight up
+    Military,
+    // The y = max faces shown flat on, with y running off down and to the left
+    Cavalier,
+    Custom {
+        x: [f64; 2],
+        y: [f64; 2],
+        z: [f64; 2],
+    },
+}
+
+// Where a unit step along each world axis lands on screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Axes {
+    pub x: Coord,
+    pub y: Coord,
+    pub z: Coord,
+}
+
+impl Projection {
+    pub fn axes(&self) -> Axes {
+        let axes = |x: [f64; 2], y: [f64; 2], z: [f64; 2]| Axes {
+            x: Coord { x: x[0], y: x[1] },
+            y: Coord { x: y[0], y: y[1] },
+            z: Coord { x: z[0], y: z[1] },
+        };
+
+        // Each is scaled so the floor is as wide on screen as it is with Dimetric
+        let sqrt_3 = 3f64.sqrt();
+        let sqrt_2 = std::f64::consts::SQRT_2;
+        let half_sqrt_2 = std::f64::consts::FRAC_1_SQRT_2;
+
+        match *self {
+            Projection::Isometric => axes(
+                [1.0, 1.0 / sqrt_3],
+                [-1.0, 1.0 / sqrt_3],
+                [0.0, -2.0 / sqrt_3],
+            ),
+            Projection::Dimetric => axes([1.0, 0.5], [-1.0, 0.5], [0.0, -1.0]),
+            Projection::Military => axes([1.0, 1.0], [-1.0, 1.0], [0.0, -sqrt_2]),
+            Projection::Cavalier => {
+                // Every axis is full length, with y at 45°
+                let unit = 2.0 / (1.0 + half_sqrt_2);
+                axes(
+                    [unit, 0.0],
+                    [-unit * half_sqrt_2, unit * half_sqrt_2],
+                    [0.0, -unit],
+                )
+            }
+            Projection::Custom { x, y, z } => axes(x, y, z),
+        }
+    }
+
+    // Custom projections have to show the floor from above, the right way round, with
+    // up going up
+    pub fn validate(&self) -> Result<(), String> {
+        let axes = self.axes();
+
+        let axis_lengths = [axes.x, axes.y, axes.z].map(|axis| axis.x.hypot(axis.y));
+        if axis_lengths.iter().any(|length| !length.is_finite()) {
+            return Err(format!("projection is {:?}, which isn't all numbers", self));
+        }
+
+        if axes.floor_determinant() <= 0.0 {
+            return Err(format!(
+                "projection is {:?}, which shows the floor edge on or from underneath",
+                self
+            ));
+        }
+
+        if axes.z.y >= 0.0 {
+            return Err(format!(
+                "projection is {:?}, whose z doesn't point up the screen",
+                self
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Axes {
+    // Where a point ends up on screen, before zooming or offsetting
+    pub fn apply(&self, x: f64, y: f64, z: f64) -> Coord {
+        Coord {
+            x: x * self.x.x + y * self.y.x + z * self.z.x,
+            y: x * self.x.y + y * self.y.y + z * self.z.y,
+        }
+    }
+
+    // The point on the floor which lands at this point on screen
+    pub fn unapply_on_floor(&self, screen: Coord) -> Coord {
+        let det = self.floor_determinant();
+
+        Coord {
+            x: (screen.x * self.y.y - self.y.x * screen.y) / det,
+            y: (self.x.x * screen.y - screen.x * self.x.y) / det,
+        }
+    }
+
+    // Which way the viewer is across the floor for every unit up: the one direction
+    // which doesn't move on screen at all
+    pub fn view_direction(&self) -> Coord {
+        let det = self.floor_determinant();
+
+        Coord {
+            x: (self.y.x * self.z.y - self.z.x * self.y.y) / det,
+            y: (self.z.x * self.x.y - self.x.x * self.z.y) / det,
+        }
+    }
+
+    // How much the floor's area is scaled on screen; negative if it's mirrored
+    fn floor_determinant(&self) -> f64 {
+        self.x.x * self.y.y - self.y.x * self.x.y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in() {
+        for projection in [
+            Projection::Isometric,
+            Projection::Dimetric,
+            Projection::Military,
+            Projection::Cavalier,
+        ] {
+            assert_eq!(projection.validate(), Ok(()));
+
+            // Moving towards the viewer doesn't move anything on screen, and getting back
+            // onto the floor undoes the projection
+            let axes = projection.axes();
+            let view = axes.view_direction();
+            let screen = axes.apply(view.x, view.y, 1.0);
+            assert!(
+                screen.x.abs() < 1e-12 && screen.y.abs() < 1e-12,
+                "{:?}",
+                projection
+            );
+
+            let floor = axes.unapply_on_floor(axes.apply(120.0, -35.0, 0.0));
+            assert!((floor.x - 120.0).abs() < 1e-9 && (floor.y + 35.0).abs() < 1e-9);
+
+            // The floor is as wide as ever
+            let width = axes.apply(100.0, 0.0, 0.0).x - axes.apply(0.0, 100.0, 0.0).x;
+            assert!((width - 200.0).abs() < 1e-9, "{:?}", projection);
+        }
+
+        let axes = Projection::Dimetric.axes();
+        assert_eq!(axes.view_direction(), Coord { x: 1.0, y: 1.0 });
+
+        // Isometric axes are all the same length
+        let axes = Projection::Isometric.axes();
+        let length = |axis: Coord| axis.x.hypot(axis.y);
+        assert!((length(axes.x) - length(axes.z)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_custom() {
+        let custom = |x, y, z| Projection::Custom { x, y, z };
+
+        assert_eq!(
+            custom([1.0, 0.25], [-1.0, 0.25], [0.0, -1.0]).validate(),
+            Ok(())
+        );
+        // Mirrored
+        assert!(custom([-1.0, 0.5], [1.0, 0.5], [0.0, -1.0])
+            .validate()
+            .unwrap_err()
+            .contains("from underneath"));
+        // Edge on
+        assert!(custom([1.0, 0.0], [-1.0, 0.0], [0.0, -1.0])
+            .validate()
+            .is_err());
+        assert!(custom([1.0, 0.5], [-1.0, 0.5], [0.0, 1.0])
+            .validate()
+            .unwrap_err()
+            .contains("doesn't point up"));
+    }
+}
@@ -0,0 +1,447 @@
+use ab_glyph::{Font, FontRef, OutlineCurve};
+use std::cell::RefCell;
+use std::f64::consts::TAU;
+use tiny_skia::{BlendMode, Color, Paint, PathBuilder, Pixmap, Stroke, Transform};
+
+use crate::render::{Composite, FillRule, LineJoin, Renderer, TextAlign, TextBaseline};
+use crate::scene::{self, Scene, SceneError};
+use crate::world::World;
+
+// What a browser on Linux draws "sans-serif" with. See fonts/LICENSE.
+const FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+// The page behind the canvas, which the links are multiplied onto
+const BACKGROUND: Color = Color::WHITE;
+
+const IDENTITY: [f64; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+// Draws into an image in memory with tiny-skia, so scenes can be turned into pictures
+// without a browser
+pub struct Raster {
+    font: FontRef<'static>,
+    state: RefCell<State>,
+}
+
+struct State {
+    pixmap: Pixmap,
+
+    // In pixels, with the transform already applied, as a canvas does
+    path: PathBuilder,
+    has_current_point: bool,
+    fill_style: Color,
+    stroke_style: Color,
+    line_width: f64,
+    line_join: Option<LineJoin>,
+    font_size: f64,
+    text_align: TextAlign,
+    transform: [f64; 6],
+    composite: Composite,
+}
+
+impl Raster {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            font: FontRef::try_from_slice(FONT).unwrap(),
+            state: RefCell::new(State::new(width, height)),
+        }
+    }
+
+    pub fn png(&self) -> Vec<u8> {
+        self.state.borrow().pixmap.encode_png().unwrap()
+    }
+
+    // The colour at a pixel, as red, green and blue
+    #[cfg(test)]
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 3] {
+        let pixel = self.state.borrow().pixmap.pixel(x, y).unwrap();
+        [pixel.red(), pixel.green(), pixel.blue()]
+    }
+}
+
+impl State {
+    fn new(width: u32, height: u32) -> Self {
+        let mut pixmap = Pixmap::new(width.max(1), height.max(1)).unwrap();
+        pixmap.fill(BACKGROUND);
+
+        State {
+            pixmap,
+            path: PathBuilder::new(),
+            has_current_point: false,
+            fill_style: Color::BLACK,
+            stroke_style: Color::BLACK,
+            line_width: 1.0,
+            line_join: None,
+            font_size: 10.0,
+            text_align: TextAlign::Left,
+            transform: IDENTITY,
+            composite: Composite::SourceOver,
+        }
+    }
+
+    fn apply(&self, x: f64, y: f64) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.transform;
+        ((a * x + c * y + e) as f32, (b * x + d * y + f) as f32)
+    }
+
+    // How much the transform scales lengths, on average
+    fn scale(&self) -> f64 {
+        let [a, b, c, d, _, _] = self.transform;
+        (a * d - b * c).abs().sqrt()
+    }
+
+    fn paint(&self, color: Color) -> Paint<'static> {
+        let mut paint = Paint::default();
+        paint.set_color(color);
+        paint.anti_alias = true;
+        paint.blend_mode = match self.composite {
+            Composite::SourceOver => BlendMode::SourceOver,
+            Composite::Multiply => BlendMode::Multiply,
+        };
+
+        paint
+    }
+}
+
+impl Renderer for Raster {
+    // Starts the image over at this size
+    fn clear(&self, width: f64, height: f64) {
+        *self.state.borrow_mut() = State::new(width.round() as u32, height.round() as u32);
+    }
+
+    fn begin_path(&self) {
+        let mut state = self.state.borrow_mut();
+        state.path = PathBuilder::new();
+        state.has_current_point = false;
+    }
+
+    fn move_to(&self, x: f64, y: f64) {
+        let mut state = self.state.borrow_mut();
+        let (x, y) = state.apply(x, y);
+        state.path.move_to(x, y);
+        state.has_current_point = true;
+    }
+
+    fn line_to(&self, x: f64, y: f64) {
+        let mut state = self.state.borrow_mut();
+        let (x, y) = state.apply(x, y);
+        if state.has_current_point {
+            state.path.line_to(x, y);
+        } else {
+            state.path.move_to(x, y);
+            state.has_current_point = true;
+        }
+    }
+
+    // Drawn as short lines, which is plenty for the small circles on the buttons and
+    // the sun
+    fn arc(&self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64) {
+        let sweep = (end_angle - start_angle).clamp(-TAU, TAU);
+        let steps = ((sweep.abs() * radius / 2.0).ceil() as usize).max(8);
+
+        for step in 0..=steps {
+            let angle = start_angle + sweep * step as f64 / steps as f64;
+            self.line_to(x + radius * angle.cos(), y + radius * angle.sin());
+        }
+    }
+
+    fn close_path(&self) {
+        self.state.borrow_mut().path.close();
+    }
+
+    fn set_fill_style(&self, style: &str) {
+        self.state.borrow_mut().fill_style = parse_color(style);
+    }
+
+    fn set_stroke_style(&self, style: &str) {
+        self.state.borrow_mut().stroke_style = parse_color(style);
+    }
+
+    fn set_line_width(&self, width: f64) {
+        self.state.borrow_mut().line_width = width;
+    }
+
+    fn set_line_join(&self, join: LineJoin) {
+        self.state.borrow_mut().line_join = Some(join);
+    }
+
+    fn fill(&self, rule: FillRule) {
+        let mut state = self.state.borrow_mut();
+        let Some(path) = state.path.clone().finish() else {
+            return;
+        };
+        let paint = state.paint(state.fill_style);
+        let rule = match rule {
+            FillRule::NonZero => tiny_skia::FillRule::Winding,
+            FillRule::EvenOdd => tiny_skia::FillRule::EvenOdd,
+        };
+
+        state
+            .pixmap
+            .fill_path(&path, &paint, rule, Transform::identity(), None);
+    }
+
+    fn stroke(&self) {
+        let mut state = self.state.borrow_mut();
+        let Some(path) = state.path.clone().finish() else {
+            return;
+        };
+        let paint = state.paint(state.stroke_style);
+        let stroke = Stroke {
+            width: (state.line_width * state.scale()) as f32,
+            line_join: match state.line_join {
+                Some(LineJoin::Round) => tiny_skia::LineJoin::Round,
+                None => tiny_skia::LineJoin::Miter,
+            },
+            ..Stroke::default()
+        };
+
+        state
+            .pixmap
+            .stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+    }
+
+    // Only the size is used, as there's only the one font
+    fn set_font(&self, font: &str) {
+        let size = font
+            .split_whitespace()
+            .find_map(|part| part.strip_suffix("px")?.parse().ok());
+        if let Some(size) = size {
+            self.state.borrow_mut().font_size = size;
+        }
+    }
+
+    fn set_text_align(&self, align: TextAlign) {
+        self.state.borrow_mut().text_align = align;
+    }
+
+    // Text is always drawn around the middle of the em box
+    fn set_text_baseline(&self, _baseline: TextBaseline) {}
+
+    fn fill_text(&self, text: &str, x: f64, y: f64) {
+        let mut state = self.state.borrow_mut();
+        let font = &self.font;
+
+        // Font units are y up, from the baseline
+        let units_per_em = font.units_per_em().unwrap_or(1000.0) as f64;
+        let scale = state.font_size / units_per_em;
+        let (ascent, descent) = (
+            font.ascent_unscaled() as f64,
+            font.descent_unscaled() as f64,
+        );
+        let middle = units_per_em * (ascent + descent) / (ascent - descent) / 2.0;
+
+        let glyphs: Vec<_> = text
+            .chars()
+            .map(|character| font.glyph_id(character))
+            .collect();
+        let mut offsets = Vec::with_capacity(glyphs.len());
+        let mut pen = 0.0;
+        for (i, glyph) in glyphs.iter().enumerate() {
+            if i > 0 {
+                pen += font.kern_unscaled(glyphs[i - 1], *glyph) as f64;
+            }
+            offsets.push(pen);
+            pen += font.h_advance_unscaled(*glyph) as f64;
+        }
+        let start = match state.text_align {
+            TextAlign::Left => x,
+            TextAlign::Center => x - pen * scale / 2.0,
+        };
+
+        let mut path = PathBuilder::new();
+        for (glyph, offset) in glyphs.iter().zip(offsets) {
+            let Some(outline) = font.outline(*glyph) else {
+                continue;
+            };
+            let point = |point: ab_glyph::Point| {
+                state.apply(
+                    start + (offset + point.x as f64) * scale,
+                    y + (middle - point.y as f64) * scale,
+                )
+            };
+
+            let mut last = None;
+            for curve in &outline.curves {
+                let (from, to) = match curve {
+                    OutlineCurve::Line(from, to) => (from, to),
+                    OutlineCurve::Quad(from, _, to) => (from, to),
+                    OutlineCurve::Cubic(from, _, _, to) => (from, to),
+                };
+                if last != Some(*from) {
+                    if last.is_some() {
+                        path.close();
+                    }
+                    let (x, y) = point(*from);
+                    path.move_to(x, y);
+                }
+                last = Some(*to);
+
+                match curve {
+                    OutlineCurve::Line(_, to) => {
+                        let (x, y) = point(*to);
+                        path.line_to(x, y);
+                    }
+                    OutlineCurve::Quad(_, control, to) => {
+                        let ((x1, y1), (x, y)) = (point(*control), point(*to));
+                        path.quad_to(x1, y1, x, y);
+                    }
+                    OutlineCurve::Cubic(_, control1, control2, to) => {
+                        let (x1, y1) = point(*control1);
+                        let ((x2, y2), (x, y)) = (point(*control2), point(*to));
+                        path.cubic_to(x1, y1, x2, y2, x, y);
+                    }
+                }
+            }
+            path.close();
+        }
+
+        let Some(path) = path.finish() else {
+            return;
+        };
+        let paint = state.paint(state.fill_style);
+        state.pixmap.fill_path(
+            &path,
+            &paint,
+            tiny_skia::FillRule::Winding,
+            Transform::identity(),
+            None,
+        );
+    }
+
+    fn set_transform(&self, transform: [f64; 6]) {
+        self.state.borrow_mut().transform = transform;
+    }
+
+    fn reset_transform(&self) {
+        self.state.borrow_mut().transform = IDENTITY;
+    }
+
+    fn set_composite(&self, composite: Composite) {
+        self.state.borrow_mut().composite = composite;
+    }
+}
+
+// Draws the built-in scene for a path as a PNG, laid out the way it starts off
+pub fn route_to_png(path: &str, width: u32, height: u32) -> Result<Vec<u8>, SceneError> {
+    let scene = Scene::for_path(path)?;
+    let world = World::still(&scene, width as f64, height as f64);
+
+    let raster = Raster::new(width, height);
+    world.draw_scene(&raster);
+
+    Ok(raster.png())
+}
+
+// What a page's preview is called under /og/: "index" for the home page and "projects"
+// for /projects. Paths without a scene of their own don't have one.
+pub fn preview_name(path: &str) -> Option<&str> {
+    if !Scene::exists(path) {
+        return None;
+    }
+
+    match scene::normalize(path) {
+        "/" => Some("index"),
+        path => Some(path.trim_start_matches('/')),
+    }
+}
+
+// Anything CSS can't make sense of is black, as on a canvas before any style is set
+fn parse_color(style: &str) -> Color {
+    csscolorparser::parse(style)
+        .ok()
+        .and_then(|color| Color::from_rgba(color.r, color.g, color.b, color.a))
+        .unwrap_or(Color::BLACK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(raster: &Raster, x: f64, y: f64, size: f64) {
+        raster.move_to(x, y);
+        raster.line_to(x + size, y);
+        raster.line_to(x + size, y + size);
+        raster.line_to(x, y + size);
+        raster.close_path();
+    }
+
+    #[test]
+    fn test_fill() {
+        let raster = Raster::new(10, 10);
+        raster.begin_path();
+        raster.move_to(2.0, 2.0);
+        raster.line_to(6.5, 2.0);
+        raster.line_to(6.5, 8.0);
+        raster.line_to(2.0, 8.0);
+        raster.close_path();
+        raster.set_fill_style("hsl(0deg, 0%, 0%)");
+        raster.fill(FillRule::NonZero);
+
+        // Inside, half covered at the right edge, and outside
+        assert_eq!(raster.pixel(3, 3), [0, 0, 0]);
+        let [edge, _, _] = raster.pixel(6, 3);
+        assert!((120..=135).contains(&edge), "{}", edge);
+        assert_eq!(raster.pixel(8, 3), [255, 255, 255]);
+
+        // Multiplying blue onto the black leaves it black, and onto white leaves blue
+        raster.set_composite(Composite::Multiply);
+        raster.begin_path();
+        square(&raster, 0.0, 0.0, 10.0);
+        raster.set_fill_style("#0000ff");
+        raster.fill(FillRule::NonZero);
+        assert_eq!(raster.pixel(3, 3), [0, 0, 0]);
+        assert_eq!(raster.pixel(8, 3), [0, 0, 255]);
+    }
+
+    #[test]
+    fn test_even_odd() {
+        // A square with a hole in it
+        let raster = Raster::new(10, 10);
+        raster.begin_path();
+        square(&raster, 0.0, 0.0, 10.0);
+        square(&raster, 3.0, 3.0, 4.0);
+        raster.set_fill_style("rgb(0, 0, 0)");
+        raster.fill(FillRule::EvenOdd);
+        assert_eq!(raster.pixel(1, 1), [0, 0, 0]);
+        assert_eq!(raster.pixel(5, 5), [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_text() {
+        // An I, centred, has its stem in the middle and nothing off to the side
+        let raster = Raster::new(40, 40);
+        raster.set_font("30px sans-serif");
+        raster.set_text_align(TextAlign::Center);
+        raster.set_fill_style("#000000");
+        raster.fill_text("I", 20.0, 20.0);
+        assert_eq!(raster.pixel(20, 20), [0, 0, 0]);
+        assert_eq!(raster.pixel(20, 10), [0, 0, 0]);
+        assert_eq!(raster.pixel(5, 20), [255, 255, 255]);
+
+        // Turned on its side by the transform, it lies along x instead
+        let raster = Raster::new(40, 40);
+        raster.set_font("30px sans-serif");
+        raster.set_text_align(TextAlign::Center);
+        raster.set_transform([0.0, 1.0, -1.0, 0.0, 20.0, 20.0]);
+        raster.fill_text("I", 0.0, 0.0);
+        assert_eq!(raster.pixel(15, 20), [0, 0, 0]);
+        assert_eq!(raster.pixel(20, 5), [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_route_to_png() {
+        let png = route_to_png("/", 300, 200).unwrap();
+        assert_eq!(&png[1..4], b"PNG");
+        assert!(matches!(
+            route_to_png("/nowhere", 300, 200),
+            Err(SceneError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_preview_name() {
+        assert_eq!(preview_name("/"), Some("index"));
+        assert_eq!(preview_name("/projects/"), Some("projects"));
+        assert_eq!(preview_name("/nowhere"), None);
+    }
+}
@@ -0,0 +1,406 @@
+#[cfg(test)]
+use std::cell::RefCell;
+
+// Everything the scene is drawn with, as a canvas-like state machine: build a path, set
+// the styles, then fill or stroke it. Positions are in canvas pixels, after whatever
+// transform is set. Methods take &self like the canvas's own do, so several closures
+// drawing a cube can share one.
+pub trait Renderer {
+    fn clear(&self, width: f64, height: f64);
+
+    fn begin_path(&self);
+    fn move_to(&self, x: f64, y: f64);
+    fn line_to(&self, x: f64, y: f64);
+    // Angles in radians, clockwise on screen from the x axis
+    fn arc(&self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64);
+    fn close_path(&self);
+
+    fn set_fill_style(&self, style: &str);
+    fn set_stroke_style(&self, style: &str);
+    fn set_line_width(&self, width: f64);
+    fn set_line_join(&self, join: LineJoin);
+    fn fill(&self, rule: FillRule);
+    fn stroke(&self);
+
+    // A CSS font, like "48px sans-serif"
+    fn set_font(&self, font: &str);
+    fn set_text_align(&self, align: TextAlign);
+    fn set_text_baseline(&self, baseline: TextBaseline);
+    fn fill_text(&self, text: &str, x: f64, y: f64);
+
+    // As in grid::Grid::flat_text_transform
+    fn set_transform(&self, transform: [f64; 6]);
+    fn reset_transform(&self);
+    fn set_composite(&self, composite: Composite);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineJoin {
+    Round,
+}
+
+// Which parts of a path with holes or crossings get filled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FillRule {
+    NonZero,
+    // Nothing fills with this at the moment, but every backend can
+    #[allow(dead_code)]
+    EvenOdd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAlign {
+    Left,
+    Center,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextBaseline {
+    Middle,
+}
+
+// How what's drawn mixes with what's already there
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Composite {
+    SourceOver,
+    Multiply,
+}
+
+// Draws straight onto a browser canvas
+pub struct Canvas2d<'a> {
+    context: &'a web_sys::CanvasRenderingContext2d,
+}
+
+impl<'a> Canvas2d<'a> {
+    pub fn new(context: &'a web_sys::CanvasRenderingContext2d) -> Self {
+        Self { context }
+    }
+}
+
+impl Renderer for Canvas2d<'_> {
+    fn clear(&self, width: f64, height: f64) {
+        self.context.clear_rect(0.0, 0.0, width, height);
+    }
+
+    fn begin_path(&self) {
+        self.context.begin_path();
+    }
+
+    fn move_to(&self, x: f64, y: f64) {
+        self.context.move_to(x, y);
+    }
+
+    fn line_to(&self, x: f64, y: f64) {
+        self.context.line_to(x, y);
+    }
+
+    fn arc(&self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64) {
+        let _ = self.context.arc(x, y, radius, start_angle, end_angle);
+    }
+
+    fn close_path(&self) {
+        self.context.close_path();
+    }
+
+    fn set_fill_style(&self, style: &str) {
+        self.context.set_fill_style_str(style);
+    }
+
+    fn set_stroke_style(&self, style: &str) {
+        self.context.set_stroke_style_str(style);
+    }
+
+    fn set_line_width(&self, width: f64) {
+        self.context.set_line_width(width);
+    }
+
+    fn set_line_join(&self, join: LineJoin) {
+        self.context.set_line_join(match join {
+            LineJoin::Round => "round",
+        });
+    }
+
+    fn fill(&self, rule: FillRule) {
+        self.context.fill_with_canvas_winding_rule(match rule {
+            FillRule::NonZero => web_sys::CanvasWindingRule::Nonzero,
+            FillRule::EvenOdd => web_sys::CanvasWindingRule::Evenodd,
+        });
+    }
+
+    fn stroke(&self) {
+        self.context.stroke();
+    }
+
+    fn set_font(&self, font: &str) {
+        self.context.set_font(font);
+    }
+
+    fn set_text_align(&self, align: TextAlign) {
+        self.context.set_text_align(match align {
+            TextAlign::Left => "left",
+            TextAlign::Center => "center",
+        });
+    }
+
+    fn set_text_baseline(&self, baseline: TextBaseline) {
+        self.context.set_text_baseline(match baseline {
+            TextBaseline::Middle => "middle",
+        });
+    }
+
+    fn fill_text(&self, text: &str, x: f64, y: f64) {
+        let _ = self.context.fill_text(text, x, y);
+    }
+
+    fn set_transform(&self, [a, b, c, d, e, f]: [f64; 6]) {
+        let _ = self.context.set_transform(a, b, c, d, e, f);
+    }
+
+    fn reset_transform(&self) {
+        let _ = self.context.reset_transform();
+    }
+
+    fn set_composite(&self, composite: Composite) {
+        let _ = self
+            .context
+            .set_global_composite_operation(match composite {
+                Composite::SourceOver => "source-over",
+                Composite::Multiply => "multiply",
+            });
+    }
+}
+
+// One call made on a Renderer
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Clear {
+        width: f64,
+        height: f64,
+    },
+    BeginPath,
+    MoveTo {
+        x: f64,
+        y: f64,
+    },
+    LineTo {
+        x: f64,
+        y: f64,
+    },
+    Arc {
+        x: f64,
+        y: f64,
+        radius: f64,
+        start_angle: f64,
+        end_angle: f64,
+    },
+    ClosePath,
+    SetFillStyle(String),
+    SetStrokeStyle(String),
+    SetLineWidth(f64),
+    SetLineJoin(LineJoin),
+    Fill(FillRule),
+    Stroke,
+    SetFont(String),
+    SetTextAlign(TextAlign),
+    SetTextBaseline(TextBaseline),
+    FillText {
+        text: String,
+        x: f64,
+        y: f64,
+    },
+    SetTransform([f64; 6]),
+    ResetTransform,
+    SetComposite(Composite),
+}
+
+// Writes down everything it's asked to draw, to check in tests
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct DisplayList {
+    commands: RefCell<Vec<Command>>,
+}
+
+#[cfg(test)]
+impl DisplayList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn commands(&self) -> Vec<Command> {
+        self.commands.borrow().clone()
+    }
+
+    // Plays everything back onto another renderer
+    pub fn replay(&self, renderer: &dyn Renderer) {
+        for command in self.commands.borrow().iter() {
+            match command {
+                Command::Clear { width, height } => renderer.clear(*width, *height),
+                Command::BeginPath => renderer.begin_path(),
+                Command::MoveTo { x, y } => renderer.move_to(*x, *y),
+                Command::LineTo { x, y } => renderer.line_to(*x, *y),
+                Command::Arc {
+                    x,
+                    y,
+                    radius,
+                    start_angle,
+                    end_angle,
+                } => renderer.arc(*x, *y, *radius, *start_angle, *end_angle),
+                Command::ClosePath => renderer.close_path(),
+                Command::SetFillStyle(style) => renderer.set_fill_style(style),
+                Command::SetStrokeStyle(style) => renderer.set_stroke_style(style),
+                Command::SetLineWidth(width) => renderer.set_line_width(*width),
+                Command::SetLineJoin(join) => renderer.set_line_join(*join),
+                Command::Fill(rule) => renderer.fill(*rule),
+                Command::Stroke => renderer.stroke(),
+                Command::SetFont(font) => renderer.set_font(font),
+                Command::SetTextAlign(align) => renderer.set_text_align(*align),
+                Command::SetTextBaseline(baseline) => renderer.set_text_baseline(*baseline),
+                Command::FillText { text, x, y } => renderer.fill_text(text, *x, *y),
+                Command::SetTransform(transform) => renderer.set_transform(*transform),
+                Command::ResetTransform => renderer.reset_transform(),
+                Command::SetComposite(composite) => renderer.set_composite(*composite),
+            }
+        }
+    }
+
+    // The text drawn, in order, with the fill style it was drawn in
+    pub fn texts(&self) -> Vec<(String, String)> {
+        let mut fill_style = String::new();
+        let mut texts = vec![];
+
+        for command in self.commands.borrow().iter() {
+            match command {
+                Command::SetFillStyle(style) => fill_style = style.clone(),
+                Command::FillText { text, .. } => texts.push((text.clone(), fill_style.clone())),
+                _ => {}
+            }
+        }
+
+        texts
+    }
+
+    fn push(&self, command: Command) {
+        self.commands.borrow_mut().push(command);
+    }
+}
+
+#[cfg(test)]
+impl Renderer for DisplayList {
+    fn clear(&self, width: f64, height: f64) {
+        self.push(Command::Clear { width, height });
+    }
+
+    fn begin_path(&self) {
+        self.push(Command::BeginPath);
+    }
+
+    fn move_to(&self, x: f64, y: f64) {
+        self.push(Command::MoveTo { x, y });
+    }
+
+    fn line_to(&self, x: f64, y: f64) {
+        self.push(Command::LineTo { x, y });
+    }
+
+    fn arc(&self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64) {
+        self.push(Command::Arc {
+            x,
+            y,
+            radius,
+            start_angle,
+            end_angle,
+        });
+    }
+
+    fn close_path(&self) {
+        self.push(Command::ClosePath);
+    }
+
+    fn set_fill_style(&self, style: &str) {
+        self.push(Command::SetFillStyle(style.to_string()));
+    }
+
+    fn set_stroke_style(&self, style: &str) {
+        self.push(Command::SetStrokeStyle(style.to_string()));
+    }
+
+    fn set_line_width(&self, width: f64) {
+        self.push(Command::SetLineWidth(width));
+    }
+
+    fn set_line_join(&self, join: LineJoin) {
+        self.push(Command::SetLineJoin(join));
+    }
+
+    fn fill(&self, rule: FillRule) {
+        self.push(Command::Fill(rule));
+    }
+
+    fn stroke(&self) {
+        self.push(Command::Stroke);
+    }
+
+    fn set_font(&self, font: &str) {
+        self.push(Command::SetFont(font.to_string()));
+    }
+
+    fn set_text_align(&self, align: TextAlign) {
+        self.push(Command::SetTextAlign(align));
+    }
+
+    fn set_text_baseline(&self, baseline: TextBaseline) {
+        self.push(Command::SetTextBaseline(baseline));
+    }
+
+    fn fill_text(&self, text: &str, x: f64, y: f64) {
+        self.push(Command::FillText {
+            text: text.to_string(),
+            x,
+            y,
+        });
+    }
+
+    fn set_transform(&self, transform: [f64; 6]) {
+        self.push(Command::SetTransform(transform));
+    }
+
+    fn reset_transform(&self) {
+        self.push(Command::ResetTransform);
+    }
+
+    fn set_composite(&self, composite: Composite) {
+        self.push(Command::SetComposite(composite));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_list() {
+        let list = DisplayList::new();
+        list.set_fill_style("red");
+        list.fill_text("A", 0.0, 0.0);
+        list.set_fill_style("blue");
+        list.begin_path();
+        list.move_to(1.0, 2.0);
+        list.fill(FillRule::EvenOdd);
+        list.fill_text("B", 10.0, 0.0);
+
+        assert_eq!(
+            list.texts(),
+            vec![
+                ("A".to_string(), "red".to_string()),
+                ("B".to_string(), "blue".to_string())
+            ]
+        );
+
+        // Playing it back records the same thing again
+        let copy = DisplayList::new();
+        list.replay(&copy);
+        assert_eq!(copy.commands(), list.commands());
+        assert_eq!(copy.commands()[4], Command::MoveTo { x: 1.0, y: 2.0 });
+    }
+}
@@ -0,0 +1,77 @@
+use crate::scene::Scene;
+
+// Works out where a link goes. If it's to one of our own scenes, returns the path to
+// swap to in place; anything else (other sites, mailto:, the blog) gets opened normally.
+pub fn internal_path(current_path: &str, url: &str) -> Option<String> {
+    let path = resolve(current_path, url)?;
+
+    if Scene::exists(&path) {
+        Some(path)
+    } else {
+        None
+    }
+}
+
+// Resolves a url relative to the current path, the way the browser would, if it
+// stays on this site. Urls with a scheme, host, query or fragment are left alone.
+fn resolve(current_path: &str, url: &str) -> Option<String> {
+    if url.starts_with("//") || url.contains(['?', '#']) {
+        return None;
+    }
+    if let Some(colon) = url.find(':') {
+        if !url[..colon].contains('/') {
+            return None;
+        }
+    }
+
+    let joined = if url.starts_with('/') {
+        url.to_string()
+    } else {
+        let directory = &current_path[..=current_path.rfind('/').unwrap_or(0)];
+        format!("{}{}", directory, url)
+    };
+
+    let mut segments: Vec<&str> = vec![];
+    for segment in joined.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    Some(format!("/{}", segments.join("/")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("/", "./projects"), Some("/projects".to_string()));
+        assert_eq!(resolve("/projects", "./"), Some("/".to_string()));
+        assert_eq!(resolve("/a/b", "../c/./d/"), Some("/c/d".to_string()));
+        assert_eq!(resolve("/projects", "/"), Some("/".to_string()));
+
+        assert_eq!(resolve("/", "mailto:art@art.cx"), None);
+        assert_eq!(resolve("/", "https://revise.io"), None);
+        assert_eq!(resolve("/", "//revise.io"), None);
+        assert_eq!(resolve("/", "./blog#top"), None);
+    }
+
+    #[test]
+    fn test_internal_path() {
+        assert_eq!(
+            internal_path("/", "./projects"),
+            Some("/projects".to_string())
+        );
+        assert_eq!(internal_path("/nope", "/"), Some("/".to_string()));
+
+        // The blog is served by the server, not a scene
+        assert_eq!(internal_path("/", "./blog"), None);
+        assert_eq!(internal_path("/", "https://github.com/artursapek"), None);
+    }
+}
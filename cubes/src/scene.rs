@@ -0,0 +1,543 @@
+use geo::Coord;
+use serde::Deserialize;
+use std::fmt;
+
+use crate::camera::CameraLimits;
+use crate::cube::Cube;
+use crate::grid::LightSource;
+use crate::layout::{self, LayoutOptions};
+use crate::light::LightMode;
+use crate::momentum;
+use crate::persistence;
+use crate::projection::Projection;
+use crate::shading::Shading;
+use crate::spring::Spring;
+
+// Scenes which are built into the site, by the path they're served at
+const SCENES: &[(&str, &str, &str)] = &[
+    ("/", "home.json", include_str!("../scenes/home.json")),
+    (
+        "/projects",
+        "projects.json",
+        include_str!("../scenes/projects.json"),
+    ),
+];
+
+// Shown for any path without a scene of its own
+const NOT_FOUND: (&str, &str) = ("404.json", include_str!("../scenes/404.json"));
+
+// Everything that's on a page: the cubes, the links on the floor, where the light
+// is, and how things behave. Positions are in iso units, like everywhere else.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    #[serde(default)]
+    pub cubes: Vec<CubeSpec>,
+
+    // Words to spell out in cubes, as well as or instead of placing them one by one
+    #[serde(default)]
+    pub text: Vec<TextSpec>,
+
+    #[serde(default)]
+    pub links: Vec<LinkSpec>,
+
+    #[serde(default)]
+    pub light: LightSource,
+
+    // How it's drawn: "dimetric" (the default), "isometric", "military", "cavalier",
+    // or { "custom": { "x": [..], "y": [..], "z": [..] } }
+    #[serde(default)]
+    pub projection: Projection,
+
+    // The cubes' colour, and how the light falls on them
+    #[serde(default)]
+    pub shading: Shading,
+
+    #[serde(default)]
+    pub options: Options,
+
+    // Hash of the file this was parsed from, to tell when it's changed
+    #[serde(skip)]
+    pub hash: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CubeSpec {
+    pub character: char,
+    pub x: f64,
+    pub y: f64,
+    pub size: f64,
+}
+
+// A block of text laid out by layout::layout_text, starting at (x, y)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TextSpec {
+    pub text: String,
+    pub x: f64,
+    pub y: f64,
+    pub size: f64,
+
+    #[serde(default)]
+    pub layout: LayoutOptions,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LinkSpec {
+    pub x: f64,
+    pub y: f64,
+    pub text: String,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Options {
+    // How quickly flung cubes slow down, see momentum::apply_friction
+    pub friction: f64,
+
+    // Pulls cubes back home after a while. Off unless the scene asks for it.
+    pub spring: Option<Spring>,
+
+    // How far visitors can zoom and pan around
+    pub camera: CameraLimits,
+
+    // Whether the light stays put, follows the pointer, or follows the time of day
+    pub light_mode: LightMode,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            friction: momentum::DEFAULT_FRICTION,
+            spring: None,
+            camera: CameraLimits::default(),
+            light_mode: LightMode::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    // No scene is served at this path
+    NotFound {
+        path: String,
+    },
+    // The file isn't valid JSON, or doesn't have the shape of a scene
+    Parse {
+        file: String,
+        error: serde_json::Error,
+    },
+    // The file parsed, but something in it doesn't make sense
+    Invalid {
+        file: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::NotFound { path } => write!(f, "no scene for path {:?}", path),
+            SceneError::Parse { file, error } => write!(f, "{}: {}", file, error),
+            SceneError::Invalid { file, message } => write!(f, "{}: {}", file, message),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl Scene {
+    // Looks up the built-in scene for a path
+    pub fn for_path(path: &str) -> Result<Self, SceneError> {
+        let (_, file, source) = SCENES
+            .iter()
+            .find(|(scene_path, _, _)| *scene_path == normalize(path))
+            .ok_or_else(|| SceneError::NotFound {
+                path: path.to_string(),
+            })?;
+
+        Self::parse(file, source)
+    }
+
+    // Like for_path, but paths without a scene get the 404 one
+    pub fn route(path: &str) -> Result<Self, SceneError> {
+        match Self::for_path(path) {
+            Err(SceneError::NotFound { .. }) => Self::parse(NOT_FOUND.0, NOT_FOUND.1),
+            result => result,
+        }
+    }
+
+    pub fn exists(path: &str) -> bool {
+        SCENES
+            .iter()
+            .any(|(scene_path, _, _)| *scene_path == normalize(path))
+    }
+
+    // `file` is only used to say where an error came from
+    pub fn parse(file: &str, source: &str) -> Result<Self, SceneError> {
+        let mut scene: Scene = serde_json::from_str(source).map_err(|error| SceneError::Parse {
+            file: file.to_string(),
+            error,
+        })?;
+
+        scene.hash = persistence::scene_hash(source);
+
+        scene.validate().map_err(|message| SceneError::Invalid {
+            file: file.to_string(),
+            message,
+        })?;
+
+        Ok(scene)
+    }
+
+    pub fn cubes(&self) -> Vec<Cube> {
+        let placed = self
+            .cubes
+            .iter()
+            .map(|spec| Cube::new(spec.character, spec.x, spec.y, spec.size));
+
+        let spelled = self.text.iter().flat_map(|spec| {
+            let origin = Coord {
+                x: spec.x,
+                y: spec.y,
+            };
+            layout::layout_text(&spec.text, origin, spec.size, &spec.layout)
+        });
+
+        placed.chain(spelled).collect()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, cube) in self.cubes.iter().enumerate() {
+            if !cube.x.is_finite() || !cube.y.is_finite() {
+                return Err(format!(
+                    "cubes[{}] ({:?}) has a bad position",
+                    i, cube.character
+                ));
+            }
+            if !(cube.size.is_finite() && cube.size > 0.0) {
+                return Err(format!(
+                    "cubes[{}] ({:?}) has size {}, which should be more than 0",
+                    i, cube.character, cube.size
+                ));
+            }
+        }
+
+        for (i, text) in self.text.iter().enumerate() {
+            if !text.x.is_finite() || !text.y.is_finite() {
+                return Err(format!("text[{}] ({:?}) has a bad position", i, text.text));
+            }
+            if !(text.size.is_finite() && text.size > 0.0) {
+                return Err(format!(
+                    "text[{}] ({:?}) has size {}, which should be more than 0",
+                    i, text.text, text.size
+                ));
+            }
+            if text.layout.spacing < 0.0 || text.layout.line_spacing < 0.0 {
+                return Err(format!(
+                    "text[{}] ({:?}) has negative spacing",
+                    i, text.text
+                ));
+            }
+            if text
+                .layout
+                .max_width
+                .is_some_and(|max_width| max_width < text.size)
+            {
+                return Err(format!(
+                    "text[{}] ({:?}) has a max_width too narrow for a single letter",
+                    i, text.text
+                ));
+            }
+        }
+
+        for (i, link) in self.links.iter().enumerate() {
+            if !link.x.is_finite() || !link.y.is_finite() {
+                return Err(format!("links[{}] ({:?}) has a bad position", i, link.text));
+            }
+            if link.url.is_empty() {
+                return Err(format!("links[{}] ({:?}) has no url", i, link.text));
+            }
+        }
+
+        if self.light.z <= 0.0 {
+            return Err(format!(
+                "light is at z = {}, which should be above the floor",
+                self.light.z
+            ));
+        }
+        if !(self.light.radius.is_finite() && self.light.radius >= 0.0) {
+            return Err(format!(
+                "light.radius is {}, which can't be negative",
+                self.light.radius
+            ));
+        }
+
+        self.projection.validate()?;
+        self.shading.validate()?;
+
+        if !(self.options.friction.is_finite() && self.options.friction >= 0.0) {
+            return Err(format!(
+                "options.friction is {}, which can't be negative",
+                self.options.friction
+            ));
+        }
+
+        if let Some(spring) = self.options.spring {
+            let values = [
+                ("stiffness", spring.stiffness),
+                ("damping", spring.damping),
+                ("idle_delay", spring.idle_delay),
+            ];
+
+            for (name, value) in values {
+                if !(value.is_finite() && value >= 0.0) {
+                    return Err(format!(
+                        "options.spring.{} is {}, which can't be negative",
+                        name, value
+                    ));
+                }
+            }
+        }
+
+        let camera = self.options.camera;
+        if !(camera.min_zoom.is_finite() && camera.min_zoom > 0.0) {
+            return Err(format!(
+                "options.camera.min_zoom is {}, which should be above 0",
+                camera.min_zoom
+            ));
+        }
+
+        if !(camera.max_zoom.is_finite() && camera.max_zoom >= camera.min_zoom) {
+            return Err(format!(
+                "options.camera.max_zoom is {}, which is below min_zoom ({})",
+                camera.max_zoom, camera.min_zoom
+            ));
+        }
+
+        if let Some(bounds) = camera.bounds {
+            if !(bounds.min_x <= bounds.max_x && bounds.min_y <= bounds.max_y) {
+                return Err(format!(
+                    "options.camera.bounds is {:?}, which has a min past its max",
+                    bounds
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// "/projects/" is the same page as "/projects"
+pub fn normalize(path: &str) -> &str {
+    match path.trim_end_matches('/') {
+        "" => "/",
+        trimmed => trimmed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_scenes() {
+        for (path, _, _) in SCENES {
+            let scene = Scene::for_path(path).unwrap();
+            assert!(!scene.cubes.is_empty());
+        }
+
+        let home = Scene::for_path("/").unwrap();
+        assert_eq!(home.cubes().len(), 27);
+        assert_eq!(home.links[1].url, "./projects");
+
+        assert!(matches!(
+            Scene::for_path("/nope"),
+            Err(SceneError::NotFound { .. })
+        ));
+        assert_eq!(Scene::route("/nope").unwrap().links[0].url, "/");
+        assert!(Scene::exists("/projects/"));
+    }
+
+    #[test]
+    fn test_defaults() {
+        let scene = Scene::parse(
+            "test.json",
+            r#"{ "cubes": [{ "character": "A", "x": 0, "y": 0, "size": 50 }] }"#,
+        )
+        .unwrap();
+
+        assert!(scene.links.is_empty());
+        assert_eq!(scene.light.z, LightSource::default().z);
+        assert_eq!(scene.options.friction, momentum::DEFAULT_FRICTION);
+    }
+
+    #[test]
+    fn test_spring_options() {
+        let scene = Scene::parse(
+            "test.json",
+            r#"{ "options": { "spring": { "stiffness": 50 } } }"#,
+        )
+        .unwrap();
+        let spring = scene.options.spring.unwrap();
+        assert_eq!(spring.stiffness, 50.0);
+        assert_eq!(spring.damping, Spring::default().damping);
+
+        let error = Scene::parse(
+            "test.json",
+            r#"{ "options": { "spring": { "idle_delay": -2 } } }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "test.json: options.spring.idle_delay is -2, which can't be negative"
+        );
+    }
+
+    #[test]
+    fn test_projection() {
+        let scene = Scene::parse("test.json", "{}").unwrap();
+        assert_eq!(scene.projection, Projection::Dimetric);
+
+        let scene = Scene::parse("test.json", r#"{ "projection": "isometric" }"#).unwrap();
+        assert_eq!(scene.projection, Projection::Isometric);
+
+        let scene = Scene::parse(
+            "test.json",
+            r#"{ "projection": { "custom": { "x": [1, 0.25], "y": [-1, 0.25], "z": [0, -1] } } }"#,
+        )
+        .unwrap();
+        assert_eq!(scene.projection.axes().x.y, 0.25);
+
+        let error = Scene::parse(
+            "test.json",
+            r#"{ "projection": { "custom": { "x": [1, 0], "y": [-1, 0], "z": [0, -1] } } }"#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("edge on"));
+        assert!(Scene::parse("test.json", r#"{ "projection": "fisheye" }"#).is_err());
+    }
+
+    #[test]
+    fn test_shading() {
+        let scene = Scene::parse(
+            "test.json",
+            r#"{ "shading": { "color": [255, 200, 0], "ambient": 0.5 } }"#,
+        )
+        .unwrap();
+        assert_eq!(scene.shading.color, [255.0, 200.0, 0.0]);
+        assert_eq!(scene.shading.ambient, 0.5);
+        assert_eq!(scene.shading.diffuse, Shading::default().diffuse);
+
+        let error = Scene::parse("test.json", r#"{ "shading": { "ambient": -1 } }"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "test.json: shading.ambient is -1, which can't be negative"
+        );
+    }
+
+    #[test]
+    fn test_camera_options() {
+        let scene = Scene::parse(
+            "test.json",
+            r#"{ "options": { "camera": { "max_zoom": 2, "bounds": {
+                "min_x": -100, "min_y": -100, "max_x": 500, "max_y": 500
+            } } } }"#,
+        )
+        .unwrap();
+        let camera = scene.options.camera;
+        assert_eq!(camera.max_zoom, 2.0);
+        assert_eq!(camera.min_zoom, CameraLimits::default().min_zoom);
+        assert_eq!(camera.bounds.unwrap().max_x, 500.0);
+
+        let error = |json: &str| Scene::parse("test.json", json).unwrap_err().to_string();
+        assert_eq!(
+            error(r#"{ "options": { "camera": { "min_zoom": 0 } } }"#),
+            "test.json: options.camera.min_zoom is 0, which should be above 0"
+        );
+        assert_eq!(
+            error(r#"{ "options": { "camera": { "min_zoom": 2, "max_zoom": 1 } } }"#),
+            "test.json: options.camera.max_zoom is 1, which is below min_zoom (2)"
+        );
+        assert!(error(
+            r#"{ "options": { "camera": { "bounds": {
+                "min_x": 10, "min_y": 0, "max_x": 0, "max_y": 0
+            } } } }"#
+        )
+        .contains("which has a min past its max"));
+    }
+
+    #[test]
+    fn test_light_mode() {
+        let parse =
+            |json: &str| Scene::parse("test.json", json).map(|scene| scene.options.light_mode);
+        assert_eq!(parse("{}").unwrap(), LightMode::Fixed);
+        assert_eq!(
+            parse(r#"{ "options": { "light_mode": "day" } }"#).unwrap(),
+            LightMode::Day
+        );
+        assert!(parse(r#"{ "options": { "light_mode": "disco" } }"#).is_err());
+    }
+
+    #[test]
+    fn test_text() {
+        let scene = Scene::parse(
+            "test.json",
+            r#"{
+                "cubes": [{ "character": "A", "x": 500, "y": 0, "size": 50 }],
+                "text": [
+                    { "text": "HI", "x": 0, "y": 100, "size": 50 },
+                    {
+                        "text": "THERE", "x": 100, "y": 100, "size": 50,
+                        "layout": { "spacing": 0, "align": "center", "jitter": { "amount": 5, "seed": 1 } }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let cubes = scene.cubes();
+        let spelled: String = cubes.iter().map(|cube| cube.character()).collect();
+        assert_eq!(spelled, "AHITHERE");
+        assert_eq!(cubes[2].coord.y, 40.0);
+
+        let error = Scene::parse(
+            "test.json",
+            r#"{ "text": [{ "text": "HI", "x": 0, "y": 0, "size": 50, "layout": { "align": "middle" } }] }"#,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("unknown variant `middle`"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |source: &str| Scene::parse("test.json", source).unwrap_err().to_string();
+
+        // Where in the file it went wrong
+        assert_eq!(
+            error("{ \"cubes\": [\n  { \"character\": \"A\", \"x\": 0, \"y\": 0 }\n] }"),
+            "test.json: missing field `size` at line 2 column 38"
+        );
+        assert!(error(r#"{ "cubes": [], "lights": {} }"#).contains("unknown field `lights`"));
+        assert!(
+            error(r#"{ "cubes": [{ "character": "AB", "x": 0, "y": 0, "size": 1 }] }"#)
+                .contains("expected a character")
+        );
+
+        assert_eq!(
+            error(r#"{ "cubes": [{ "character": "A", "x": 0, "y": 0, "size": -5 }] }"#),
+            "test.json: cubes[0] ('A') has size -5, which should be more than 0"
+        );
+        assert_eq!(
+            error(r#"{ "light": { "x": 0, "y": 0, "z": 500, "radius": -10 } }"#),
+            "test.json: light.radius is -10, which can't be negative"
+        );
+        assert_eq!(
+            error(r#"{ "cubes": [], "options": { "friction": -1 } }"#),
+            "test.json: options.friction is -1, which can't be negative"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use serde::Deserialize;
+
+use crate::grid::LightSource;
+
+// How brightly each face of a cube is lit. A face gets the ambient light whichever way
+// it faces, plus diffuse light the more squarely it faces the light (Lambert's law).
+// Faces turned away from the light only get the ambient.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Shading {
+    // The cubes' colour as red, green and blue from 0 to 255, as lit by 1.0
+    pub color: [f64; 3],
+    pub ambient: f64,
+    pub diffuse: f64,
+    // How dark shadows on the floor are, from 0 (not there) to 1 (solid)
+    pub floor_shadow: f64,
+}
+
+impl Default for Shading {
+    fn default() -> Self {
+        Self {
+            color: [232.0, 236.0, 241.0],
+            ambient: 0.75,
+            diffuse: 0.35,
+            floor_shadow: 0.05,
+        }
+    }
+}
+
+impl Shading {
+    // How lit a face is, given which way it faces and its middle (iso units)
+    pub fn brightness(&self, normal: [f64; 3], center: [f64; 3], light: &LightSource) -> f64 {
+        let to_light = [
+            light.x - center[0],
+            light.y - center[1],
+            light.z - center[2],
+        ];
+        let distance = to_light.iter().map(|d| d * d).sum::<f64>().sqrt();
+
+        let facing = if distance > 0.0 {
+            (0..3).map(|i| normal[i] * to_light[i]).sum::<f64>() / distance
+        } else {
+            0.0
+        };
+
+        self.ambient + self.diffuse * facing.max(0.0)
+    }
+
+    pub fn face_color(&self, normal: [f64; 3], center: [f64; 3], light: &LightSource) -> String {
+        self.color_at(self.brightness(normal, center, light))
+    }
+
+    // The colour of any part of a face in another cube's shadow, which only the ambient
+    // light reaches
+    pub fn shadow_color(&self) -> String {
+        self.color_at(self.ambient)
+    }
+
+    fn color_at(&self, brightness: f64) -> String {
+        let [r, g, b] = self
+            .color
+            .map(|channel| (channel * brightness).round().clamp(0.0, 255.0));
+
+        format!("rgb({}, {}, {})", r, g, b)
+    }
+
+    // The colour to fill each of `layers` overlapping floor shadows with, so that where
+    // they all overlap it's as dark as floor_shadow
+    pub fn floor_shadow_color(&self, layers: usize) -> String {
+        let alpha = 1.0 - (1.0 - self.floor_shadow).powf(1.0 / layers.max(1) as f64);
+
+        format!("rgba(41, 66, 82, {})", alpha)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for (i, channel) in self.color.iter().enumerate() {
+            if !(0.0..=255.0).contains(channel) {
+                return Err(format!(
+                    "shading.color[{}] is {}, which should be from 0 to 255",
+                    i, channel
+                ));
+            }
+        }
+
+        for (name, value) in [("ambient", self.ambient), ("diffuse", self.diffuse)] {
+            if !(value.is_finite() && value >= 0.0) {
+                return Err(format!(
+                    "shading.{} is {}, which can't be negative",
+                    name, value
+                ));
+            }
+        }
+
+        if !(0.0..=1.0).contains(&self.floor_shadow) {
+            return Err(format!(
+                "shading.floor_shadow is {}, which should be from 0 to 1",
+                self.floor_shadow
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brightness() {
+        let shading = Shading::default();
+        let light = LightSource {
+            x: 0.0,
+            y: 0.0,
+            z: 500.0,
+            radius: 0.0,
+        };
+
+        // Right under the light, the top is lit head on and the sides only by ambient light
+        let top = shading.brightness([0.0, 0.0, 1.0], [0.0, 0.0, 100.0], &light);
+        assert_eq!(top, shading.ambient + shading.diffuse);
+        let side = shading.brightness([1.0, 0.0, 0.0], [-50.0, 0.0, 50.0], &light);
+        assert!(side > shading.ambient && side < top);
+        let away = shading.brightness([-1.0, 0.0, 0.0], [-50.0, 0.0, 50.0], &light);
+        assert_eq!(away, shading.ambient);
+
+        // Which is what shadows cast on a face are coloured too
+        assert_eq!(
+            shading.face_color([-1.0, 0.0, 0.0], [-50.0, 0.0, 50.0], &light),
+            shading.shadow_color()
+        );
+
+        // Moving the light round to the side lights that side up more than the top
+        let light = LightSource {
+            x: 1000.0,
+            y: 0.0,
+            z: 60.0,
+            radius: 0.0,
+        };
+        let top = shading.brightness([0.0, 0.0, 1.0], [0.0, 0.0, 100.0], &light);
+        let side = shading.brightness([1.0, 0.0, 0.0], [50.0, 0.0, 50.0], &light);
+        assert!(side > top);
+
+        let flat = Shading {
+            color: [100.0, 200.0, 250.0],
+            ambient: 1.0,
+            diffuse: 0.0,
+            ..Shading::default()
+        };
+        assert_eq!(
+            flat.face_color([0.0, 0.0, 1.0], [0.0, 0.0, 0.0], &light),
+            "rgb(100, 200, 250)"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(Shading::default().validate(), Ok(()));
+
+        let shading = Shading {
+            color: [0.0, 300.0, 0.0],
+            ..Shading::default()
+        };
+        assert_eq!(
+            shading.validate().unwrap_err(),
+            "shading.color[1] is 300, which should be from 0 to 255"
+        );
+
+        let shading = Shading {
+            diffuse: -1.0,
+            ..Shading::default()
+        };
+        assert_eq!(
+            shading.validate().unwrap_err(),
+            "shading.diffuse is -1, which can't be negative"
+        );
+
+        // Overlapping layers build up to floor_shadow
+        let alpha = |color: String| {
+            let alpha = color.trim_end_matches(')').rsplit(", ").next().unwrap();
+            alpha.parse::<f64>().unwrap()
+        };
+        let layer = alpha(Shading::default().floor_shadow_color(8));
+        assert!((1.0 - (1.0 - layer).powi(8) - 0.05).abs() < 1e-12);
+        assert!((alpha(Shading::default().floor_shadow_color(1)) - 0.05).abs() < 1e-12);
+
+        let shading = Shading {
+            floor_shadow: 1.5,
+            ..Shading::default()
+        };
+        assert_eq!(
+            shading.validate().unwrap_err(),
+            "shading.floor_shadow is 1.5, which should be from 0 to 1"
+        );
+    }
+}
@@ -0,0 +1,444 @@
+use geo::{Area, ConvexHull, Coord, LineString, MultiPoint, Point, Polygon, Rect};
+
+use crate::cube::Cube;
+use crate::grid::LightSource;
+
+// Keeps the parts of a cube we project through the light from reaching the light itself,
+// where they'd be thrown off to infinity
+const NEAR_LIGHT: f64 = 1.0;
+
+// How many points round the edge of a light with a radius soft shadows are cast from
+const LIGHT_SAMPLES: usize = 8;
+
+// One flat face of a cube, to work out which parts of it other cubes cast shadows on.
+// It lies at `at` along world axis `axis` (0, 1, 2 for x, y, z), facing towards bigger
+// values along it if `facing` is 1 and smaller if it's -1. Points on it are given by
+// their other two coordinates, in order (so (y, z) for a face along x).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Surface {
+    pub axis: usize,
+    pub at: f64,
+    pub facing: f64,
+    pub rect: Rect,
+}
+
+impl Surface {
+    // The 3D point for a point on the surface
+    pub fn world_point(&self, point: Coord) -> [f64; 3] {
+        let (u, v) = other_axes(self.axis);
+        let mut world = [0.0; 3];
+        world[self.axis] = self.at;
+        world[u] = point.x;
+        world[v] = point.y;
+
+        world
+    }
+}
+
+// The parts of `surface` the light can't reach because one of `cubes` is in the way,
+// as polygons on it. They can overlap, and like the floor shadows they all wind the
+// same way, so filling them together with the nonzero rule doesn't darken the overlaps.
+// Faces turned away from the light are all in shadow, but that's left to their shading.
+pub fn shadows_on(surface: &Surface, cubes: &[Cube], light: &LightSource) -> Vec<Polygon> {
+    cubes
+        .iter()
+        .filter_map(|cube| cast(cube, light, surface.axis, surface.at, surface.facing))
+        .filter_map(|shadow| clip(&shadow, &surface.rect))
+        .collect()
+}
+
+// The shadow a cube casts on the floor, as a polygon in iso units. There's always one
+// unless the cube is wholly above the light (or the light is down on the floor), and
+// it's exact however the cube lines up with the light.
+pub fn on_floor(cube: &Cube, light: &LightSource) -> Option<Polygon> {
+    cast(cube, light, 2, 0.0, 1.0)
+}
+
+// All the cubes' floor shadows, once for each point the light is cast from: just the
+// one for a point light, or a ring of them round the edge of a bigger one. Where every
+// layer overlaps none of the light gets through; further out only some of them do, so
+// the shadow fades out, more so the further it falls from the cube.
+//
+// The shadows in a layer aren't merged, as geo's union can panic on perfectly good
+// layouts. Filled together as one path with the nonzero rule, they come out the same.
+pub fn floor_shadows(cubes: &[Cube], light: &LightSource) -> Vec<Vec<Polygon>> {
+    light_samples(light)
+        .iter()
+        .map(|light| {
+            cubes
+                .iter()
+                .filter_map(|cube| on_floor(cube, light))
+                .collect()
+        })
+        .collect()
+}
+
+// Points spread round a light's edge, across the floor
+fn light_samples(light: &LightSource) -> Vec<LightSource> {
+    if light.radius <= 0.0 {
+        return vec![*light];
+    }
+
+    (0..LIGHT_SAMPLES)
+        .map(|i| {
+            let angle = i as f64 / LIGHT_SAMPLES as f64 * std::f64::consts::TAU;
+            LightSource {
+                x: light.x + light.radius * angle.cos(),
+                y: light.y + light.radius * angle.sin(),
+                ..*light
+            }
+        })
+        .collect()
+}
+
+// The outline of the shadow the part of `cube` between a plane and the light casts on
+// that plane, as points on the plane (see Surface for how they're given). The shadow of
+// a box is convex, so it's the hull of where the light through each corner lands.
+fn cast(cube: &Cube, light: &LightSource, axis: usize, at: f64, facing: f64) -> Option<Polygon> {
+    let light = [light.x, light.y, light.z];
+    if (light[axis] - at) * facing <= NEAR_LIGHT {
+        return None;
+    }
+
+    let (mut min, mut max) = extents(cube);
+    let near_light = light[axis] - NEAR_LIGHT * facing;
+    if facing > 0.0 {
+        min[axis] = min[axis].max(at);
+        max[axis] = max[axis].min(near_light);
+    } else {
+        min[axis] = min[axis].max(near_light);
+        max[axis] = max[axis].min(at);
+    }
+    if min[axis] >= max[axis] {
+        return None;
+    }
+
+    let (u, v) = other_axes(axis);
+    let points: Vec<Point> = (0..8)
+        .map(|corner| {
+            let point = [0, 1, 2].map(|i| {
+                if corner & (1 << i) == 0 {
+                    min[i]
+                } else {
+                    max[i]
+                }
+            });
+            let t = (at - light[axis]) / (point[axis] - light[axis]);
+
+            Point::new(
+                light[u] + (point[u] - light[u]) * t,
+                light[v] + (point[v] - light[v]) * t,
+            )
+        })
+        .collect();
+
+    Some(MultiPoint::new(points).convex_hull())
+}
+
+// The part of a convex polygon inside a rectangle, cut down one edge of it at a time.
+// Anything that only touches the rectangle's edge doesn't count.
+fn clip(polygon: &Polygon, rect: &Rect) -> Option<Polygon> {
+    let (min, max) = (rect.min(), rect.max());
+    let mut points: Vec<Coord> = polygon.exterior().coords().copied().collect();
+    points.pop();
+
+    // Each edge as whether it bounds x or y, where, and which side of it is kept
+    let edges = [
+        (true, min.x, 1.0),
+        (true, max.x, -1.0),
+        (false, min.y, 1.0),
+        (false, max.y, -1.0),
+    ];
+    for (is_x, at, keep) in edges {
+        let coordinate = |point: Coord| if is_x { point.x } else { point.y };
+        let inside = |point: Coord| (coordinate(point) - at) * keep >= 0.0;
+        let mut clipped = Vec::with_capacity(points.len() + 1);
+
+        for (i, &point) in points.iter().enumerate() {
+            let previous = points[(i + points.len() - 1) % points.len()];
+            if inside(point) != inside(previous) {
+                let t = (at - coordinate(previous)) / (coordinate(point) - coordinate(previous));
+                clipped.push(previous + (point - previous) * t);
+            }
+            if inside(point) {
+                clipped.push(point);
+            }
+        }
+
+        points = clipped;
+        if points.len() < 3 {
+            return None;
+        }
+    }
+
+    let clipped = Polygon::new(LineString::from(points), vec![]);
+    if clipped.unsigned_area() > 1e-9 {
+        Some(clipped)
+    } else {
+        None
+    }
+}
+
+fn other_axes(axis: usize) -> (usize, usize) {
+    match axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        _ => (0, 1),
+    }
+}
+
+fn extents(cube: &Cube) -> ([f64; 3], [f64; 3]) {
+    let min = cube.bounds.min();
+    let max = cube.bounds.max();
+
+    ([min.x, min.y, cube.z], [max.x, max.y, cube.top()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::{BooleanOps, BoundingRect, MultiPolygon, Winding};
+
+    fn top_surface(cube: &Cube) -> Surface {
+        Surface {
+            axis: 2,
+            at: cube.top(),
+            facing: 1.0,
+            rect: cube.bounds,
+        }
+    }
+
+    fn area(shadows: &[Polygon]) -> f64 {
+        shadows.iter().map(|shadow| shadow.unsigned_area()).sum()
+    }
+
+    #[test]
+    fn test_tall_next_to_short() {
+        let light = LightSource {
+            x: -1000.0,
+            y: 50.0,
+            z: 2000.0,
+            radius: 0.0,
+        };
+
+        // A short cube with a tower of two right next to it, between it and the light
+        let short = Cube::new('A', 0.0, 0.0, 100.0);
+        let mut tower = vec![
+            Cube::new('B', -100.0, 0.0, 100.0),
+            Cube::new('C', -100.0, 0.0, 100.0),
+        ];
+        tower[1].z = 100.0;
+
+        // Part of the short cube's top is shaded by the upper cube of the tower. The
+        // light is off to the left, so the shadow reaches in from the left edge.
+        let shadows = shadows_on(&top_surface(&short), &tower, &light);
+        assert_eq!(shadows.len(), 1);
+        let area = area(&shadows);
+        assert!(area > 0.0 && area < 100.0 * 100.0, "{}", area);
+        let bounds = shadows[0].bounding_rect().unwrap();
+        assert!(bounds.min().x.abs() < 1e-9);
+        assert!(bounds.max().x < 100.0);
+
+        // The side facing the tower is turned towards the light, and right up against
+        // the lower cube, so it's all in shadow
+        let side = Surface {
+            axis: 0,
+            at: 0.0,
+            facing: -1.0,
+            rect: Rect::new(Coord { x: 0.0, y: 0.0 }, Coord { x: 100.0, y: 100.0 }),
+        };
+        let shadows = shadows_on(&side, &tower, &light);
+        assert!(shadows
+            .iter()
+            .any(|shadow| (shadow.unsigned_area() - 100.0 * 100.0).abs() < 1e-6));
+
+        // The tower's top is higher than anything around it, so it's all lit, as is
+        // the short cube's top with nothing next to it. A cube doesn't shade itself.
+        let all = [short, tower[0], tower[1]];
+        assert!(shadows_on(&top_surface(&tower[1]), &all, &light).is_empty());
+        assert!(shadows_on(&top_surface(&short), &[short], &light).is_empty());
+
+        // And the far side faces away from the light, which is left to shading
+        let far_side = Surface {
+            facing: 1.0,
+            at: 100.0,
+            ..side
+        };
+        assert!(shadows_on(&far_side, &tower, &light).is_empty());
+    }
+
+    #[test]
+    fn test_clip() {
+        let rect = Rect::new(Coord { x: 0.0, y: 0.0 }, Coord { x: 10.0, y: 10.0 });
+        let diamond = Polygon::new(
+            LineString::from(vec![(5.0, -2.0), (12.0, 5.0), (5.0, 12.0), (-2.0, 5.0)]),
+            vec![],
+        );
+
+        // The diamond's corners poke out past every edge, and get cut off
+        let clipped = clip(&diamond, &rect).unwrap();
+        assert!((clipped.unsigned_area() - (100.0 - 4.0 * 4.5)).abs() < 1e-9);
+        assert_eq!(clipped.exterior().is_ccw(), diamond.exterior().is_ccw());
+
+        // Just touching the edge, or off to the side, there's nothing left
+        let beside = Rect::new(Coord { x: 10.0, y: 0.0 }, Coord { x: 20.0, y: 10.0 });
+        assert!(clip(&beside.to_polygon(), &rect).is_none());
+        let away = Rect::new(Coord { x: 30.0, y: 0.0 }, Coord { x: 40.0, y: 10.0 });
+        assert!(clip(&away.to_polygon(), &rect).is_none());
+    }
+
+    #[test]
+    fn test_world_point() {
+        let face = Surface {
+            axis: 1,
+            at: 40.0,
+            facing: 1.0,
+            rect: Rect::new(Coord { x: 0.0, y: 0.0 }, Coord { x: 10.0, y: 10.0 }),
+        };
+        assert_eq!(face.world_point(Coord { x: 3.0, y: 7.0 }), [3.0, 40.0, 7.0]);
+    }
+
+    #[test]
+    fn test_floor_sweep() {
+        let light = LightSource {
+            x: 0.0,
+            y: 0.0,
+            z: 500.0,
+            radius: 0.0,
+        };
+        let covers = |shadow: &Polygon, points: &[[f64; 2]]| {
+            let outline = MultiPoint::new(points.iter().map(|&[x, y]| Point::new(x, y)).collect())
+                .convex_hull();
+            (shadow.union(&outline).unsigned_area() - shadow.unsigned_area()).abs() < 1e-6
+        };
+
+        // All round the light, including lined up with it on either axis and right under
+        // it, on the floor and up in the air
+        for z in [0.0, 100.0] {
+            for x in (-300..=300).step_by(50) {
+                for y in (-300..=300).step_by(50) {
+                    let mut cube = Cube::new('A', x as f64, y as f64, 100.0);
+                    cube.z = z;
+                    let shadow = on_floor(&cube, &light)
+                        .unwrap_or_else(|| panic!("no shadow at {:?}", cube.coord));
+
+                    // It covers where the light through the top corners lands, and the
+                    // footprint too when it's sitting on the floor
+                    let scale = light.z / (light.z - cube.top());
+                    let (min, max) = (cube.bounds.min(), cube.bounds.max());
+                    let corners = [
+                        [min.x, min.y],
+                        [max.x, min.y],
+                        [max.x, max.y],
+                        [min.x, max.y],
+                    ];
+                    let top = corners.map(|[x, y]| [x * scale, y * scale]);
+                    assert!(covers(&shadow, &top), "{:?} {}", cube.coord, z);
+                    if z == 0.0 {
+                        assert!(covers(&shadow, &corners), "{:?}", cube.coord);
+                    }
+
+                    // And it doesn't jump about as the cube moves across the light's axes
+                    let mut nudged = Cube::new('A', x as f64 + 1e-9, y as f64 - 1e-9, 100.0);
+                    nudged.z = z;
+                    let area = on_floor(&nudged, &light).unwrap().unsigned_area();
+                    assert!((area - shadow.unsigned_area()).abs() < 1e-3);
+                }
+            }
+        }
+
+        // Right under the light, the shadow is just the top blown up
+        let cube = Cube::new('A', -50.0, -50.0, 100.0);
+        let area = on_floor(&cube, &light).unwrap().unsigned_area();
+        assert!((area - 125.0 * 125.0).abs() < 1e-6, "{}", area);
+
+        // Poking up past the light, only the part below it casts a shadow, and a cube
+        // that's all above it doesn't cast one at all
+        let mut cube = Cube::new('A', 200.0, 0.0, 100.0);
+        cube.z = 450.0;
+        assert!(on_floor(&cube, &light).is_some());
+        cube.z = 500.0;
+        assert!(on_floor(&cube, &light).is_none());
+    }
+
+    #[test]
+    fn test_floor_shadows() {
+        let light = LightSource {
+            x: 0.0,
+            y: 0.0,
+            z: 500.0,
+            radius: 0.0,
+        };
+        let cubes = [
+            Cube::new('A', 100.0, 0.0, 100.0),
+            Cube::new('B', 200.0, 0.0, 100.0),
+        ];
+
+        // A point light casts one layer, with a shadow for each cube
+        let layers = floor_shadows(&cubes, &light);
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].len(), 2);
+
+        // A bigger light casts from all round its edge. Only the middle of the shadow
+        // is in every layer, and some layers reach past the point light's one.
+        let soft = LightSource {
+            radius: 50.0,
+            ..light
+        };
+        let point = MultiPolygon::new(layers[0].clone());
+        let layers = floor_shadows(&cubes, &soft);
+        assert_eq!(layers.len(), LIGHT_SAMPLES);
+        let merged = |layer: &Vec<Polygon>| {
+            layer
+                .iter()
+                .fold(MultiPolygon::new(vec![]), |merged, shadow| {
+                    merged.union(&MultiPolygon::new(vec![shadow.clone()]))
+                })
+        };
+        let umbra = layers
+            .iter()
+            .skip(1)
+            .fold(merged(&layers[0]), |umbra, layer| {
+                umbra.intersection(&merged(layer))
+            });
+        let penumbra = layers
+            .iter()
+            .skip(1)
+            .fold(merged(&layers[0]), |penumbra, layer| {
+                penumbra.union(&merged(layer))
+            });
+        assert!(umbra.unsigned_area() < merged(&point.0).unsigned_area());
+        assert!(penumbra.unsigned_area() > merged(&point.0).unsigned_area());
+
+        // The fade is wider the further the shadow falls from the cube
+        let width = |layers: &[Vec<Polygon>]| {
+            let max_x = |shadows: &Vec<Polygon>| {
+                let shadows = MultiPolygon::new(shadows.clone());
+                shadows.bounding_rect().unwrap().max().x
+            };
+            let far = layers.iter().map(max_x).fold(f64::MIN, f64::max);
+            let near = layers.iter().map(max_x).fold(f64::MAX, f64::min);
+            far - near
+        };
+        let mut stacked = Cube::new('C', 200.0, 0.0, 100.0);
+        stacked.z = 100.0;
+        let tall = [cubes[0], cubes[1], stacked];
+        assert!(width(&floor_shadows(&tall, &soft)) > width(&layers));
+    }
+
+    #[test]
+    fn test_floor_shadows_dont_panic() {
+        // Merging these shadows with geo's union used to panic, depending on the order
+        // the cubes came in
+        let cubes = [
+            Cube::new('A', -200.0, -300.0, 50.0),
+            Cube::new('B', 250.0, -300.0, 100.0),
+            Cube::new('C', -200.0, -50.0, 50.0),
+            Cube::new('D', -150.0, -250.0, 100.0),
+            Cube::new('E', 100.0, -50.0, 50.0),
+        ];
+        let layers = floor_shadows(&cubes, &LightSource::default());
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].len(), cubes.len());
+    }
+}
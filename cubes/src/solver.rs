@@ -0,0 +1,195 @@
+use std::cmp::Ordering;
+
+use crate::Cube;
+
+// Upper bound on relaxation passes per frame. Each pass fully separates every
+// overlapping pair it finds, so in practice even a long chain push settles in a handful.
+pub const MAX_ITERATIONS: usize = 32;
+
+#[derive(Debug, PartialEq)]
+pub enum Solution {
+    // No cubes overlap. Holds the number of passes it took to get there.
+    Resolved { iterations: usize },
+    // We hit the iteration limit; these pairs of cube ids still overlap.
+    GaveUp { overlapping: Vec<(usize, usize)> },
+}
+
+// Iterative constraint solver which separates overlapping cubes until none are left.
+//
+// Cubes in `pinned` (the ones being dragged) never move. For every other overlapping
+// pair, the cube further from the nearest pinned cube gets pushed out of the way, so
+// pushes ripple outward from whatever is being dragged. The push is along whichever
+// axis has the smaller overlap, since that's the shortest way out.
+pub fn solve(cubes: &mut [Cube], pinned: &[usize], max_iterations: usize) -> Solution {
+    for iteration in 0..max_iterations {
+        let mut any_overlap = false;
+
+        for i in 0..cubes.len() {
+            for j in (i + 1)..cubes.len() {
+                let (overlap_x, overlap_y) = cubes[i].overlap(&cubes[j]);
+
+                if overlap_x <= 0.0 || overlap_y <= 0.0 {
+                    continue;
+                }
+
+                any_overlap = true;
+
+                let (mover, anchor) = match pick_mover(cubes, pinned, i, j) {
+                    Some(pair) => pair,
+                    // Two pinned cubes on top of each other; nothing we're allowed to do
+                    None => continue,
+                };
+
+                let (anchor_cube, mover_cube) = (cubes[anchor], cubes[mover]);
+                let (overlap_x, overlap_y) = anchor_cube.overlap(&mover_cube);
+
+                // Same convention as Cube::overlap: the overlap is measured from the cube
+                // with the lower coordinate on each axis.
+                let sign_x = if anchor_cube.coord.x <= mover_cube.coord.x {
+                    1.0
+                } else {
+                    -1.0
+                };
+                let sign_y = if anchor_cube.coord.y <= mover_cube.coord.y {
+                    1.0
+                } else {
+                    -1.0
+                };
+
+                if overlap_x < overlap_y {
+                    cubes[mover].translate(sign_x * overlap_x, 0.0);
+                } else {
+                    cubes[mover].translate(0.0, sign_y * overlap_y);
+                }
+            }
+        }
+
+        if !any_overlap {
+            return Solution::Resolved {
+                iterations: iteration,
+            };
+        }
+    }
+
+    let overlapping = overlapping_pairs(cubes);
+
+    if overlapping.is_empty() {
+        Solution::Resolved {
+            iterations: max_iterations,
+        }
+    } else {
+        Solution::GaveUp { overlapping }
+    }
+}
+
+pub fn overlapping_pairs(cubes: &[Cube]) -> Vec<(usize, usize)> {
+    let mut pairs = vec![];
+
+    for i in 0..cubes.len() {
+        for j in (i + 1)..cubes.len() {
+            let (overlap_x, overlap_y) = cubes[i].overlap(&cubes[j]);
+
+            if overlap_x > 0.0 && overlap_y > 0.0 {
+                pairs.push((cubes[i].id, cubes[j].id));
+            }
+        }
+    }
+
+    pairs
+}
+
+// Decide which of two overlapping cubes (by index) gets moved.
+// Returns (mover, anchor), or None if neither is allowed to move.
+fn pick_mover(cubes: &[Cube], pinned: &[usize], i: usize, j: usize) -> Option<(usize, usize)> {
+    match (pinned.contains(&cubes[i].id), pinned.contains(&cubes[j].id)) {
+        (true, true) => None,
+        (true, false) => Some((j, i)),
+        (false, true) => Some((i, j)),
+        (false, false) => {
+            let distance_i = distance_to_pinned(cubes, pinned, &cubes[i]);
+            let distance_j = distance_to_pinned(cubes, pinned, &cubes[j]);
+
+            match distance_i.partial_cmp(&distance_j) {
+                Some(Ordering::Greater) => Some((i, j)),
+                _ => Some((j, i)),
+            }
+        }
+    }
+}
+
+fn distance_to_pinned(cubes: &[Cube], pinned: &[usize], cube: &Cube) -> f64 {
+    let center = cube.bounds.center();
+
+    cubes
+        .iter()
+        .filter(|other| pinned.contains(&other.id))
+        .map(|other| {
+            let other_center = other.bounds.center();
+            (center.x - other_center.x).hypot(center.y - other_center.y)
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(id: usize, x: f64, y: f64, size: f64) -> Cube {
+        let mut cube = Cube::new('A', x, y, size);
+        cube.id = id;
+        cube
+    }
+
+    #[test]
+    fn test_solve_chain() {
+        // A dragged cube shoved into a row of three touching cubes
+        let mut cubes = vec![
+            cube(1, 50.0, 0.0, 100.0),
+            cube(2, 100.0, 0.0, 100.0),
+            cube(3, 200.0, 0.0, 100.0),
+            cube(4, 300.0, 0.0, 100.0),
+        ];
+
+        let solution = solve(&mut cubes, &[1], MAX_ITERATIONS);
+        assert!(matches!(solution, Solution::Resolved { .. }));
+        assert!(overlapping_pairs(&cubes).is_empty());
+
+        // The dragged cube stays put and everything else is pushed along
+        assert_eq!(cubes[0].coord.x, 50.0);
+        assert_eq!(cubes[1].coord.x, 150.0);
+        assert_eq!(cubes[2].coord.x, 250.0);
+        assert_eq!(cubes[3].coord.x, 350.0);
+    }
+
+    #[test]
+    fn test_solve_cluster() {
+        // A tight cluster of mixed-size cubes all overlapping each other
+        let mut cubes = vec![
+            cube(1, 0.0, 0.0, 100.0),
+            cube(2, 40.0, 30.0, 60.0),
+            cube(3, 80.0, -20.0, 60.0),
+            cube(4, 20.0, 90.0, 100.0),
+            cube(5, 60.0, 60.0, 60.0),
+        ];
+
+        let solution = solve(&mut cubes, &[1], MAX_ITERATIONS);
+        assert!(matches!(solution, Solution::Resolved { .. }));
+        assert!(overlapping_pairs(&cubes).is_empty());
+        assert_eq!(cubes[0].coord.x, 0.0);
+        assert_eq!(cubes[0].coord.y, 0.0);
+    }
+
+    #[test]
+    fn test_solve_gives_up() {
+        // Two pinned cubes can't be separated, and we say so
+        let mut cubes = vec![cube(1, 0.0, 0.0, 100.0), cube(2, 50.0, 50.0, 100.0)];
+
+        let solution = solve(&mut cubes, &[1, 2], MAX_ITERATIONS);
+        assert_eq!(
+            solution,
+            Solution::GaveUp {
+                overlapping: vec![(1, 2)]
+            }
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use geo::Coord;
+use serde::Deserialize;
+
+use crate::momentum;
+
+// Once a cube is this close to home (iso units) and barely moving, it snaps the rest
+// of the way, rather than creeping in forever
+const SNAP_DISTANCE: f64 = 0.5;
+
+// If a cube heading home hasn't got any closer for this long (ms), it's boxed in,
+// usually by cubes that need to get past it the other way. It hops over them instead.
+pub const HOP_DELAY: f64 = 600.0;
+
+// Anything less than this (iso units) doesn't count as getting closer to home
+const MIN_PROGRESS: f64 = 0.001;
+
+// Pulls cubes back to where the scene put them after they've been left alone for
+// a while, so a scattered name puts itself back together.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Spring {
+    // How hard a cube is pulled towards home, per unit of distance (per second²).
+    // Higher is snappier.
+    pub stiffness: f64,
+    // How quickly a cube's speed is bled off on the way (per second). Below
+    // 2 * sqrt(stiffness) cubes overshoot home a little before settling.
+    pub damping: f64,
+    // How long nobody has to touch anything before cubes start heading home (seconds)
+    pub idle_delay: f64,
+}
+
+impl Default for Spring {
+    fn default() -> Self {
+        Self {
+            stiffness: 20.0,
+            damping: 7.0,
+            idle_delay: 8.0,
+        }
+    }
+}
+
+impl Spring {
+    // The velocity (iso units per ms) of a cube at `position` after being pulled
+    // towards `home` for `dt` ms
+    pub fn pull(&self, position: Coord, home: Coord, velocity: Coord, dt: f64) -> Coord {
+        // Everything is in ms, but the constants are per second
+        let stiffness = self.stiffness / 1_000_000.0;
+        let damping = self.damping / 1000.0;
+
+        let acceleration = |offset: f64, velocity: f64| stiffness * offset - damping * velocity;
+
+        Coord {
+            x: velocity.x + acceleration(home.x - position.x, velocity.x) * dt,
+            y: velocity.y + acceleration(home.y - position.y, velocity.y) * dt,
+        }
+    }
+
+    pub fn is_idle(&self, idle_time: f64) -> bool {
+        idle_time >= self.idle_delay * 1000.0
+    }
+}
+
+// How a cube heading home is getting on
+#[derive(Debug, Clone, Copy)]
+pub struct Homing {
+    // The closest it's got to home so far (iso units). Cubes jostling each other can
+    // each gain a little on their own turn and lose it on the other's, so this is what
+    // counts as progress, rather than the last step.
+    closest: f64,
+    stuck_time: f64,
+    // Going over the top of everything else. Only one cube hops at a time, or they'd
+    // just get in each other's way up there instead.
+    pub is_hopping: bool,
+}
+
+impl Default for Homing {
+    fn default() -> Self {
+        Self {
+            closest: f64::INFINITY,
+            stuck_time: 0.0,
+            is_hopping: false,
+        }
+    }
+}
+
+impl Homing {
+    // Keeps track of whether a cube that's `distance` iso units from home got any
+    // closer in the last `dt` ms
+    pub fn track(&mut self, distance: f64, dt: f64) {
+        if distance < self.closest - MIN_PROGRESS {
+            self.closest = distance;
+            self.stuck_time = 0.0;
+        } else {
+            self.stuck_time += dt;
+        }
+    }
+
+    // Hasn't got any closer for long enough that it should hop
+    pub fn is_stuck(&self) -> bool {
+        self.stuck_time >= HOP_DELAY
+    }
+}
+
+pub fn distance(a: Coord, b: Coord) -> f64 {
+    (a.x - b.x).hypot(a.y - b.y)
+}
+
+// Close enough to home, and slow enough, to call it there
+pub fn has_arrived(position: Coord, home: Coord, velocity: Coord) -> bool {
+    (home.x - position.x).abs() < SNAP_DISTANCE
+        && (home.y - position.y).abs() < SNAP_DISTANCE
+        && momentum::is_at_rest(velocity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use geo::coord;
+
+    #[test]
+    fn test_pull() {
+        let spring = Spring::default();
+        let home = coord! { x: 0.0, y: 0.0 };
+
+        let mut position = coord! { x: 300.0, y: -200.0 };
+        let mut velocity = coord! { x: 0.0, y: 0.0 };
+        let mut frames = 0;
+
+        while !has_arrived(position, home, velocity) {
+            velocity = spring.pull(position, home, velocity, 16.0);
+            position.x += velocity.x * 16.0;
+            position.y += velocity.y * 16.0;
+
+            // Never flies off further than it started
+            assert!(position.x.abs() <= 300.0 && position.y.abs() <= 200.0);
+
+            frames += 1;
+            assert!(frames < 1000);
+        }
+
+        // Takes a moment, rather than snapping straight back
+        assert!(frames > 30);
+    }
+
+    #[test]
+    fn test_homing() {
+        let mut homing = Homing::default();
+        homing.track(100.0, 16.0);
+        homing.track(90.0, 500.0);
+        homing.track(90.0, 500.0);
+        assert!(!homing.is_stuck());
+
+        // Getting closer starts the clock over, but only if it's closer than ever
+        homing.track(80.0, 16.0);
+        homing.track(85.0, 300.0);
+        homing.track(80.0, 200.0);
+        assert!(!homing.is_stuck());
+        homing.track(81.0, 100.0);
+        assert!(homing.is_stuck());
+    }
+
+    #[test]
+    fn test_idle() {
+        let spring = Spring {
+            idle_delay: 2.0,
+            ..Spring::default()
+        };
+        assert!(!spring.is_idle(1999.0));
+        assert!(spring.is_idle(2000.0));
+    }
+}
@@ -0,0 +1,340 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+
+use crate::render::{Composite, FillRule, LineJoin, Renderer, TextAlign, TextBaseline};
+use crate::scene::{Scene, SceneError};
+use crate::world::World;
+
+// The page behind the canvas, which the links are multiplied onto
+const BACKGROUND: &str = "#ffffff";
+
+const IDENTITY: [f64; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+// Draws into an SVG document instead of onto a canvas. Each fill, stroke and bit of
+// text becomes an element of its own, in the order they're drawn, so it's painted in
+// the same order too. Sizes are in canvas pixels.
+#[derive(Debug, Default)]
+pub struct Svg {
+    state: RefCell<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    width: f64,
+    height: f64,
+    elements: String,
+
+    path: String,
+    fill_style: String,
+    stroke_style: String,
+    line_width: f64,
+    line_join: Option<LineJoin>,
+    font: String,
+    text_align: Option<TextAlign>,
+    text_baseline: Option<TextBaseline>,
+    transform: Option<[f64; 6]>,
+    composite: Option<Composite>,
+}
+
+impl Svg {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The whole document, with everything drawn so far
+    pub fn finish(&self) -> String {
+        let state = self.state.borrow();
+        let (width, height) = (num(state.width), num(state.height));
+
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n\
+             <rect width=\"{width}\" height=\"{height}\" fill=\"{BACKGROUND}\"/>\n\
+             {}</svg>\n",
+            state.elements
+        )
+    }
+}
+
+impl State {
+    // What every element shares: where it's moved to and how it's blended
+    fn attributes(&self) -> String {
+        let mut attributes = String::new();
+
+        if let Some(transform) = self.transform.filter(|transform| *transform != IDENTITY) {
+            let _ = write!(
+                attributes,
+                " transform=\"matrix({})\"",
+                transform.map(|value| rounded(value, 4)).join(" ")
+            );
+        }
+        if self.composite == Some(Composite::Multiply) {
+            attributes.push_str(" style=\"mix-blend-mode: multiply\"");
+        }
+
+        attributes
+    }
+}
+
+impl Renderer for Svg {
+    // Starts the document over at this size
+    fn clear(&self, width: f64, height: f64) {
+        *self.state.borrow_mut() = State {
+            width,
+            height,
+            ..State::default()
+        };
+    }
+
+    fn begin_path(&self) {
+        self.state.borrow_mut().path.clear();
+    }
+
+    fn move_to(&self, x: f64, y: f64) {
+        let _ = write!(self.state.borrow_mut().path, "M{} {}", num(x), num(y));
+    }
+
+    fn line_to(&self, x: f64, y: f64) {
+        let _ = write!(self.state.borrow_mut().path, "L{} {}", num(x), num(y));
+    }
+
+    fn arc(&self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64) {
+        let point = |angle: f64| {
+            format!(
+                "{} {}",
+                num(x + radius * angle.cos()),
+                num(y + radius * angle.sin())
+            )
+        };
+        let sweep = end_angle - start_angle;
+        let mut state = self.state.borrow_mut();
+        let command = if state.path.is_empty() { "M" } else { "L" };
+        let _ = write!(state.path, "{}{}", command, point(start_angle));
+
+        // A whole circle has to be drawn as two halves, as it starts and ends at the
+        // same point
+        let r = num(radius);
+        if sweep >= std::f64::consts::TAU {
+            let middle = start_angle + std::f64::consts::PI;
+            let _ = write!(state.path, "A{r} {r} 0 0 1 {}", point(middle));
+            let _ = write!(state.path, "A{r} {r} 0 0 1 {}", point(start_angle));
+        } else {
+            let large = if sweep > std::f64::consts::PI { 1 } else { 0 };
+            let _ = write!(state.path, "A{r} {r} 0 {large} 1 {}", point(end_angle));
+        }
+    }
+
+    fn close_path(&self) {
+        self.state.borrow_mut().path.push('Z');
+    }
+
+    fn set_fill_style(&self, style: &str) {
+        self.state.borrow_mut().fill_style = style.to_string();
+    }
+
+    fn set_stroke_style(&self, style: &str) {
+        self.state.borrow_mut().stroke_style = style.to_string();
+    }
+
+    fn set_line_width(&self, width: f64) {
+        self.state.borrow_mut().line_width = width;
+    }
+
+    fn set_line_join(&self, join: LineJoin) {
+        self.state.borrow_mut().line_join = Some(join);
+    }
+
+    fn fill(&self, rule: FillRule) {
+        let mut state = self.state.borrow_mut();
+        let rule = match rule {
+            FillRule::NonZero => "nonzero",
+            FillRule::EvenOdd => "evenodd",
+        };
+        let element = format!(
+            "<path d=\"{}\" fill=\"{}\" fill-rule=\"{}\"{}/>\n",
+            state.path,
+            escape(&state.fill_style),
+            rule,
+            state.attributes()
+        );
+        state.elements.push_str(&element);
+    }
+
+    fn stroke(&self) {
+        let mut state = self.state.borrow_mut();
+        let join = match state.line_join {
+            Some(LineJoin::Round) => " stroke-linejoin=\"round\"",
+            None => "",
+        };
+        let element = format!(
+            "<path d=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\"{}{}/>\n",
+            state.path,
+            escape(&state.stroke_style),
+            num(state.line_width),
+            join,
+            state.attributes()
+        );
+        state.elements.push_str(&element);
+    }
+
+    fn set_font(&self, font: &str) {
+        self.state.borrow_mut().font = font.to_string();
+    }
+
+    fn set_text_align(&self, align: TextAlign) {
+        self.state.borrow_mut().text_align = Some(align);
+    }
+
+    fn set_text_baseline(&self, baseline: TextBaseline) {
+        self.state.borrow_mut().text_baseline = Some(baseline);
+    }
+
+    fn fill_text(&self, text: &str, x: f64, y: f64) {
+        let mut state = self.state.borrow_mut();
+
+        // Canvas fonts are like "48px sans-serif"
+        let (size, family) = state.font.split_once(' ').unwrap_or(("10px", "sans-serif"));
+        let anchor = match state.text_align {
+            Some(TextAlign::Center) => " text-anchor=\"middle\"",
+            Some(TextAlign::Left) | None => "",
+        };
+        let baseline = match state.text_baseline {
+            Some(TextBaseline::Middle) => " dominant-baseline=\"central\"",
+            None => "",
+        };
+
+        let element = format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"{}\" fill=\"{}\"{}{}{}>{}</text>\n",
+            num(x),
+            num(y),
+            escape(size),
+            escape(family),
+            escape(&state.fill_style),
+            anchor,
+            baseline,
+            state.attributes(),
+            escape(text)
+        );
+        state.elements.push_str(&element);
+    }
+
+    fn set_transform(&self, transform: [f64; 6]) {
+        self.state.borrow_mut().transform = Some(transform);
+    }
+
+    fn reset_transform(&self) {
+        self.state.borrow_mut().transform = None;
+    }
+
+    fn set_composite(&self, composite: Composite) {
+        self.state.borrow_mut().composite = Some(composite);
+    }
+}
+
+// Draws a scene as an SVG, laid out the way the scene starts off, without needing a
+// browser. `file` is only used to say where an error came from.
+pub fn scene_to_svg(
+    file: &str,
+    source: &str,
+    width: f64,
+    height: f64,
+) -> Result<String, SceneError> {
+    let scene = Scene::parse(file, source)?;
+    let world = World::still(&scene, width, height);
+
+    let svg = Svg::new();
+    world.draw_scene(&svg);
+
+    Ok(svg.finish())
+}
+
+// Numbers rounded to a hundredth of a pixel, without any trailing zeros
+fn num(value: f64) -> String {
+    rounded(value, 2)
+}
+
+fn rounded(value: f64, places: usize) -> String {
+    let rounded = format!("{:.*}", places, value);
+    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+
+    match trimmed {
+        "-0" => "0".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elements() {
+        let svg = Svg::new();
+        svg.clear(200.0, 100.0);
+        svg.begin_path();
+        svg.move_to(0.0, 0.5);
+        svg.line_to(10.0, 20.126);
+        svg.close_path();
+        svg.set_fill_style("rgb(1, 2, 3)");
+        svg.fill(FillRule::EvenOdd);
+
+        svg.set_transform([2.0, 0.0, 0.0, 2.0, 5.0, -1.0]);
+        svg.set_font("24px sans-serif");
+        svg.set_text_align(TextAlign::Center);
+        svg.set_composite(Composite::Multiply);
+        svg.fill_text("<A>", 0.0, 0.0);
+
+        let document = svg.finish();
+        assert!(document
+            .starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"200\" height=\"100\""));
+        assert!(document.contains(
+            "<path d=\"M0 0.5L10 20.13Z\" fill=\"rgb(1, 2, 3)\" fill-rule=\"evenodd\"/>"
+        ));
+        assert!(document.contains(
+            "<text x=\"0\" y=\"0\" font-size=\"24px\" font-family=\"sans-serif\" fill=\"rgb(1, 2, 3)\" \
+             text-anchor=\"middle\" transform=\"matrix(2 0 0 2 5 -1)\" \
+             style=\"mix-blend-mode: multiply\">&lt;A&gt;</text>"
+        ));
+    }
+
+    #[test]
+    fn test_arc() {
+        let svg = Svg::new();
+        svg.begin_path();
+        svg.arc(10.0, 10.0, 5.0, 0.0, std::f64::consts::TAU);
+        svg.set_stroke_style("red");
+        svg.set_line_width(1.0);
+        svg.stroke();
+
+        assert!(svg
+            .finish()
+            .contains("d=\"M15 10A5 5 0 0 1 5 10A5 5 0 0 1 15 10\""));
+    }
+
+    #[test]
+    fn test_scene_to_svg() {
+        let source = r#"{
+            "cubes": [
+                { "character": "A", "x": 0, "y": 0, "size": 100 },
+                { "character": "B", "x": 200, "y": 0, "size": 100 }
+            ],
+            "links": [{ "x": 0, "y": 300, "text": "Q&A", "url": "/qa" }]
+        }"#;
+        let svg = scene_to_svg("test.json", source, 800.0, 600.0).unwrap();
+
+        // Painted back to front: the link on the floor, then the cubes' letters, and none
+        // of the buttons
+        let position = |text: &str| svg.find(text).unwrap();
+        assert!(position(">Q&amp;A<") < position(">A<"));
+        assert!(position(">A<") < position(">B<"));
+        assert!(!svg.contains("↻"));
+
+        assert!(scene_to_svg("test.json", "{ \"cubes\": 1 }", 800.0, 600.0).is_err());
+    }
+}
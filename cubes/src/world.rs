@@ -0,0 +1,1668 @@
+use geo::Coord;
+use std::collections::{HashMap, HashSet};
+
+use crate::controls::Control;
+use crate::cube::{Corners, Cube};
+use crate::depth;
+use crate::gravity;
+use crate::grid::{Grid, LightSource};
+use crate::light::{self, LightMode};
+use crate::link::Link;
+use crate::momentum;
+use crate::movement::Movement;
+use crate::persistence::SavedLayout;
+use crate::picking;
+use crate::pointer::{Gesture, Grab, PointerInput, Pointers};
+use crate::render::{FillRule, Renderer};
+use crate::scene::Scene;
+use crate::shadow;
+use crate::solver::{self, Solution};
+use crate::spring::{self, Homing, Spring};
+
+// All of the simulation state: cubes, links, the grid they sit on, and what the
+// pointer is up to. This knows nothing about the browser; CubeGrid feeds it input
+// and draws the result, and tests can drive it directly.
+pub struct World {
+    pub cubes: Vec<Cube>,
+    pub links: Vec<Link>,
+    pub grid: Grid,
+
+    // Cubes from the last scene, on their way off the bottom of the screen.
+    // They're only drawn; nothing can touch them.
+    pub leaving: Vec<Cube>,
+
+    next_id: usize,
+
+    friction: f64,
+    spring: Option<Spring>,
+
+    // How long it's been since anyone touched anything (ms)
+    idle_time: f64,
+    // Cubes on their way home, by id
+    homing: HashMap<usize, Homing>,
+
+    pointers: Pointers,
+    // Where the two-finger pan and zoom was as of the last step
+    gesture: Option<Gesture>,
+    // Where the mouse or pen is when it's hovering rather than pressed (CSS pixels)
+    hover: Option<Coord>,
+    cursor: Cursor,
+    // The on-screen control the mouse is over
+    pub active_control: Option<Control>,
+
+    pub light_mode: LightMode,
+    // The sun is being dragged, or the mouse is over it
+    pub is_light_active: bool,
+
+    // A scene was just loaded, so the next step should draw it
+    scene_changed: bool,
+    scene_hash: u64,
+
+    // The visitor has moved cubes since the layout last settled
+    layout_changed: bool,
+}
+
+// A new scene's cubes drop in from this high above their spots, each a little higher
+// than the last, so they land one after another
+const DROP_HEIGHT: f64 = 700.0;
+const DROP_STAGGER: f64 = 25.0;
+
+// Room left round the edge of a still picture (px)
+const STILL_MARGIN: f64 = 24.0;
+
+// Input for a single step, already translated out of whatever DOM event it came from.
+// Positions are in CSS pixels and times are in ms, as the browser reports them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    Pointer(PointerInput),
+    // The browser took a pointer away mid-press (pointercancel, or lost capture)
+    PointerCancel {
+        id: i32,
+    },
+    // The mouse or pen moved off the canvas, so there's nothing under it any more
+    PointerLeave,
+    // The window lost focus, so whatever's pressed won't hear about being let go
+    Blur,
+    Resize {
+        width: f64,
+        height: f64,
+        device_pixel_ratio: f64,
+    },
+    // Moves the camera by (dx, dy) CSS pixels
+    Pan {
+        dx: f64,
+        dy: f64,
+    },
+    // Zooms the camera around a point on the screen (CSS pixels)
+    Zoom {
+        factor: f64,
+        position: Coord,
+    },
+    ResetCamera,
+    // Turns the view by some quarter turns, clockwise on screen
+    Rotate {
+        quarter_turns: i32,
+    },
+    // The local time of day, from 0 to 24, for the light to follow in Day mode
+    Clock {
+        hours: f64,
+    },
+    NextLightMode,
+}
+
+// What came out of a step, for the browser side to act on
+#[derive(Debug, Default)]
+pub struct Frame {
+    // Something moved or changed size, so the scene needs drawing again
+    pub redraw: bool,
+    pub cursor: &'static str,
+    // A link was clicked
+    pub open_url: Option<String>,
+    // The visitor moved some cubes, and they've all come to rest
+    pub layout_settled: bool,
+    // The solver couldn't separate these cubes (by id) this step
+    pub unresolved: Vec<(usize, usize)>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum Cursor {
+    #[default]
+    Default,
+    Grab,
+    Link,
+}
+
+impl Cursor {
+    fn to_css_string(self, is_active: bool) -> &'static str {
+        match (self, is_active) {
+            (Cursor::Default, _) => "default",
+            (Cursor::Grab, false) => "grab",
+            (Cursor::Grab, true) => "grabbing",
+            (Cursor::Link, _) => "pointer",
+        }
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl World {
+    pub fn new() -> Self {
+        World {
+            cubes: Vec::new(),
+            links: Vec::new(),
+            grid: Grid::default(),
+
+            leaving: Vec::new(),
+
+            next_id: 1,
+
+            friction: momentum::DEFAULT_FRICTION,
+            spring: None,
+
+            idle_time: 0.0,
+            homing: HashMap::new(),
+
+            pointers: Pointers::default(),
+            gesture: None,
+            hover: None,
+            cursor: Cursor::Default,
+            active_control: None,
+
+            light_mode: LightMode::Fixed,
+            is_light_active: false,
+
+            scene_changed: false,
+            scene_hash: 0,
+
+            layout_changed: false,
+        }
+    }
+
+    // A scene laid out the way it starts off, on a screen this size, for drawing
+    // somewhere other than the page. There's no canvas to measure the links with, so
+    // their widths are estimated.
+    pub fn still(scene: &Scene, width: f64, height: f64) -> Self {
+        let links = scene
+            .links
+            .iter()
+            .map(|link| {
+                let width = Link::estimate_width(&link.text);
+                Link::new(link.x, link.y, link.text.clone(), link.url.clone(), width)
+            })
+            .collect();
+
+        let mut world = World::new();
+        world.load_scene(scene, links, None, false);
+        world.step(
+            &[Input::Resize {
+                width,
+                height,
+                device_pixel_ratio: 1.0,
+            }],
+            0.0,
+        );
+
+        // The page's view is made for a browser window, so it's zoomed to fit whatever
+        // size this is instead: every corner of every cube, the links and the shadows
+        let mut points: Vec<(f64, f64, f64)> = world
+            .cubes
+            .iter()
+            .flat_map(|cube| {
+                let Corners { tl, tr, br, bl } = cube.corners();
+                [cube.z, cube.top()]
+                    .into_iter()
+                    .flat_map(move |z| [tl, tr, br, bl].map(|corner| (corner.x, corner.y, z)))
+            })
+            .collect();
+        for link in &world.links {
+            let (min, max) = (link.area().min(), link.area().max());
+            for (x, y) in [
+                (min.x, min.y),
+                (max.x, min.y),
+                (max.x, max.y),
+                (min.x, max.y),
+            ] {
+                points.push((x, y, 0.0));
+            }
+        }
+        for layer in shadow::floor_shadows(&world.cubes, &world.grid.light_source) {
+            for polygon in layer {
+                points.extend(polygon.exterior().coords().map(|c| (c.x, c.y, 0.0)));
+            }
+        }
+        world.grid.fit(&points, STILL_MARGIN);
+
+        world
+    }
+
+    // Replaces whatever's on screen with a scene, with the cubes where they were saved
+    // if there's a layout for it. When animated, the old cubes fall away through the
+    // floor and the new ones drop in from above.
+    pub fn load_scene(
+        &mut self,
+        scene: &Scene,
+        links: Vec<Link>,
+        saved: Option<&SavedLayout>,
+        animate: bool,
+    ) {
+        let outgoing = std::mem::take(&mut self.cubes);
+
+        if animate {
+            for mut cube in outgoing {
+                cube.is_active = false;
+                cube.velocity = Coord { x: 0.0, y: 0.0 };
+                self.leaving.push(cube);
+            }
+        }
+
+        // Ids start over with every scene, so they're the same every time it's
+        // loaded and saved layouts can refer to them
+        self.next_id = 1;
+        self.insert_cubes(scene.cubes());
+
+        if let Some(saved) = saved {
+            saved.apply(&mut self.cubes);
+        }
+
+        if animate {
+            for cube in &mut self.cubes {
+                cube.z += DROP_HEIGHT + cube.id as f64 * DROP_STAGGER;
+            }
+        }
+
+        self.links = links;
+        self.scene_hash = scene.hash;
+        self.layout_changed = false;
+        self.friction = scene.options.friction;
+        self.spring = scene.options.spring;
+        self.idle_time = 0.0;
+        self.homing.clear();
+        self.grid.light_source = scene.light;
+        self.light_mode = scene.options.light_mode;
+        self.is_light_active = false;
+        self.grid.projection = scene.projection;
+        self.grid.shading = scene.shading;
+        self.grid.camera.limits = scene.options.camera;
+        self.grid.camera.pivot = scene_center(&self.cubes);
+        self.grid.reset_camera();
+
+        // Whatever was being dragged is gone
+        self.pointers.clear();
+        self.gesture = None;
+        self.cursor = Cursor::Default;
+
+        self.scene_changed = true;
+    }
+
+    // Sends every cube which has been moved back to where the scene put it. They're
+    // dropped back in, like when the scene first loaded.
+    pub fn reset_layout(&mut self) {
+        for cube in &mut self.cubes {
+            if cube.coord == cube.home && cube.z == 0.0 {
+                continue;
+            }
+
+            cube.translate(cube.home.x - cube.coord.x, cube.home.y - cube.coord.y);
+            cube.z = DROP_HEIGHT + cube.id as f64 * DROP_STAGGER;
+            cube.velocity = Coord { x: 0.0, y: 0.0 };
+            cube.velocity_z = 0.0;
+            cube.is_active = false;
+        }
+
+        self.pointers.clear();
+        self.gesture = None;
+
+        self.layout_changed = false;
+        self.scene_changed = true;
+        self.idle_time = 0.0;
+        self.homing.clear();
+    }
+
+    // Every cube is where the scene puts it, as it is once the spring has pulled them
+    // all back, so there's no layout worth keeping
+    pub fn is_home(&self) -> bool {
+        self.cubes
+            .iter()
+            .all(|cube| cube.coord == cube.home && cube.z == 0.0)
+    }
+
+    pub fn saved_layout(&self) -> SavedLayout {
+        SavedLayout::new(self.scene_hash, &self.cubes)
+    }
+
+    // Draws everything, back to front
+    pub fn draw(&self, renderer: &dyn Renderer) {
+        let grid = &self.grid;
+
+        self.draw_scene(renderer);
+
+        if self.light_mode == LightMode::Fixed {
+            light::draw_handle(renderer, grid, self.is_light_active);
+        }
+
+        for control in Control::ALL {
+            control.draw(renderer, grid, self.active_control == Some(control));
+        }
+    }
+
+    // Just the scene, without the sun or buttons to move things around with
+    pub fn draw_scene(&self, renderer: &dyn Renderer) {
+        let grid = &self.grid;
+
+        renderer.clear(
+            grid.width * grid.device_pixel_ratio,
+            grid.height * grid.device_pixel_ratio,
+        );
+
+        // The last scene's cubes are falling through the floor, so everything else
+        // goes on top of them
+        for cube in self.leaving.iter().rev() {
+            cube.draw(renderer, grid, &self.leaving);
+        }
+
+        self.draw_floor_shadows(renderer);
+
+        for link in &self.links {
+            link.draw(renderer, grid);
+        }
+
+        for cube in self.cubes.iter().rev() {
+            cube.draw(renderer, grid, &self.cubes);
+        }
+    }
+
+    // The floor shadows, in a layer for each point the light is cast from. Each layer
+    // is filled as one path, so overlapping shadows don't get any darker.
+    fn draw_floor_shadows(&self, renderer: &dyn Renderer) {
+        let grid = &self.grid;
+        let layers = shadow::floor_shadows(&self.cubes, &grid.light_source);
+
+        renderer.set_fill_style(&grid.shading.floor_shadow_color(layers.len()));
+
+        for layer in layers {
+            renderer.begin_path();
+            for polygon in &layer {
+                for (i, coord) in polygon.exterior().coords().enumerate() {
+                    let coord = grid.project(coord.x, coord.y, 0.0);
+                    if i == 0 {
+                        renderer.move_to(coord.x, coord.y);
+                    } else {
+                        renderer.line_to(coord.x, coord.y);
+                    }
+                }
+                renderer.close_path();
+            }
+            // The shadows all wind the same way, so where they overlap is filled once
+            renderer.fill(FillRule::NonZero);
+        }
+    }
+
+    fn is_light_held(&self) -> bool {
+        self.pointers
+            .down
+            .iter()
+            .any(|pointer| pointer.light_offset.is_some())
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        !self.pointers.held_cubes().is_empty()
+    }
+
+    pub fn insert_cubes(&mut self, cubes: Vec<Cube>) {
+        for mut cube in cubes {
+            cube.id = self.next_id;
+            self.next_id += 1;
+            self.cubes.push(cube);
+        }
+
+        self.sort_cubes();
+    }
+
+    // Advances the simulation by `dt` ms, after applying the inputs which came in since
+    // the last step.
+    pub fn step(&mut self, inputs: &[Input], dt: f64) -> Frame {
+        let mut frame = Frame::default();
+
+        let dimens_changed = self.handle_resize(inputs);
+        let events_changed = self.handle_input_events(inputs);
+
+        self.idle_time += dt;
+        if !self.pointers.down.is_empty() {
+            self.idle_time = 0.0;
+        }
+
+        let cubes_sliding = match self.spring {
+            Some(spring) if spring.is_idle(self.idle_time) => {
+                self.step_springs(spring, dt, &mut frame)
+            }
+            _ => {
+                // Anything that was hopping home gets dropped where it is
+                self.homing.clear();
+                self.step_momentum(dt, &mut frame)
+            }
+        };
+        let cubes_falling = self.step_gravity(dt);
+        let cubes_leaving = self.step_leaving(dt);
+        let view_turning = self.grid.step_rotation(dt);
+        let scene_changed = std::mem::take(&mut self.scene_changed);
+
+        if dimens_changed
+            || events_changed
+            || view_turning
+            || cubes_sliding
+            || cubes_falling
+            || cubes_leaving
+            || scene_changed
+        {
+            self.handle_pointers(&mut frame);
+            self.sort_cubes();
+            frame.redraw = true;
+        }
+
+        if self.layout_changed && !self.is_dragging() && self.is_at_rest() {
+            self.layout_changed = false;
+            frame.layout_settled = true;
+        }
+
+        let is_pressed = self
+            .pointers
+            .down
+            .iter()
+            .any(|pointer| pointer.kind.can_hover());
+        frame.cursor = self.cursor.to_css_string(is_pressed);
+        frame
+    }
+
+    // Nothing is sliding, and nothing is in the air
+    fn is_at_rest(&self) -> bool {
+        self.cubes.iter().all(|cube| {
+            momentum::is_at_rest(cube.velocity)
+                && cube.z == gravity::support_height(cube, &self.cubes)
+        })
+    }
+
+    fn handle_resize(&mut self, inputs: &[Input]) -> bool {
+        let mut changed = false;
+
+        for input in inputs {
+            if let Input::Resize {
+                width,
+                height,
+                device_pixel_ratio,
+            } = input
+            {
+                changed |= self
+                    .grid
+                    .update_dimensions(*width, *height, *device_pixel_ratio);
+            }
+        }
+
+        changed
+    }
+
+    fn handle_input_events(&mut self, inputs: &[Input]) -> bool {
+        let mut changed = false;
+
+        for input in inputs {
+            match input {
+                Input::Pointer(input) => {
+                    if input.kind.can_hover() {
+                        self.hover = if input.is_pressed {
+                            None
+                        } else {
+                            Some(input.position)
+                        };
+                    }
+
+                    let (x, y) = self
+                        .grid
+                        .cartesian_to_iso(input.position.x, input.position.y);
+                    self.pointers.update(input, Coord { x, y });
+                    changed = true;
+
+                    // The flashlight hangs over the mouse, or a finger while it's down
+                    if self.light_mode == LightMode::Flashlight
+                        && (input.kind.can_hover() || input.is_pressed)
+                    {
+                        self.grid.light_source.x = x;
+                        self.grid.light_source.y = y;
+                    }
+                }
+                Input::PointerCancel { id } => {
+                    self.pointers.cancel(*id);
+                    changed = true;
+                }
+                Input::PointerLeave => {
+                    self.hover = None;
+                    changed = true;
+                }
+                Input::Blur => {
+                    self.pointers.cancel_all();
+                    self.hover = None;
+                    changed = true;
+                }
+                Input::Pan { dx, dy } => {
+                    self.grid.pan_by(*dx, *dy);
+                    changed = true;
+                }
+                Input::Zoom { factor, position } => {
+                    self.grid.zoom_at(*factor, *position);
+                    changed = true;
+                }
+                Input::ResetCamera => {
+                    self.grid.reset_camera();
+                    changed = true;
+                }
+                Input::Rotate { quarter_turns } => {
+                    self.grid.rotate_by(*quarter_turns);
+                    changed = true;
+                }
+                Input::Clock { hours } => {
+                    if self.light_mode == LightMode::Day {
+                        // The sun's as big as the scene's light
+                        let light = LightSource {
+                            radius: self.grid.light_source.radius,
+                            ..light::day_position(*hours, self.grid.camera.pivot)
+                        };
+                        changed |= light != self.grid.light_source;
+                        self.grid.light_source = light;
+                    }
+                }
+                Input::NextLightMode => {
+                    self.light_mode = self.light_mode.next();
+                    changed = true;
+                }
+                Input::Resize { .. } => {}
+            }
+        }
+
+        changed
+    }
+    fn sort_cubes(&mut self) {
+        let view = self.grid.view_direction();
+        depth::sort(&mut self.cubes, view);
+        depth::sort(&mut self.leaving, view);
+    }
+
+    // Every pointer drags its own cube. They all move together in one go, so when two
+    // of them push into the same cubes it comes out the same whichever moved first.
+    fn handle_pointers(&mut self, frame: &mut Frame) {
+        // Pointers which just came down grab the cube they landed on, unless another
+        // pointer already has it
+        for i in 0..self.pointers.down.len() {
+            if !self.pointers.down[i].is_new {
+                continue;
+            }
+
+            let held = self.pointers.held_cubes();
+            let light_held = self.is_light_held();
+            let pointer = &mut self.pointers.down[i];
+            pointer.is_new = false;
+
+            // The controls sit on top of everything, and then the sun
+            if Control::hit_test(&pointer.current_position, &self.grid).is_some() {
+                continue;
+            }
+
+            if self.light_mode == LightMode::Fixed
+                && !light_held
+                && light::handle_hit_test(&pointer.current_position, &self.grid)
+            {
+                let light = self.grid.light_source;
+                let on_plane = self
+                    .grid
+                    .unproject_at_z(self.grid.to_device(&pointer.current_position), light.z);
+                pointer.light_offset = Some(Coord {
+                    x: light.x - on_plane.x,
+                    y: light.y - on_plane.y,
+                });
+                continue;
+            }
+
+            let Some(pick) = picking::pick(&self.cubes, &self.grid, &pointer.current_position)
+                .filter(|pick| !held.contains(&pick.id))
+            else {
+                continue;
+            };
+
+            if let Some(cube) = self.cubes.iter().find(|cube| cube.id == pick.id) {
+                pointer.grab = Some(Grab {
+                    cube: cube.id,
+                    offset: Coord {
+                        x: pick.hit.x - cube.coord.x,
+                        y: pick.hit.y - cube.coord.y,
+                    },
+                    height: pick.hit.z - cube.z,
+                });
+            }
+        }
+
+        self.pointers.mark_gestures();
+
+        // Two fingers on empty space pan along with where they are, and pinch to zoom
+        let gesture = self.pointers.gesture();
+        if let (Some(last), Some(gesture)) = (self.gesture, gesture) {
+            if last.ids == gesture.ids {
+                self.grid.pan_by(
+                    gesture.midpoint.x - last.midpoint.x,
+                    gesture.midpoint.y - last.midpoint.y,
+                );
+                if last.distance > 0.0 && gesture.distance > 0.0 {
+                    self.grid
+                        .zoom_at(gesture.distance / last.distance, gesture.midpoint);
+                }
+            }
+        }
+        self.gesture = gesture;
+
+        let held = self.pointers.held_cubes();
+
+        // The sun moves around at the height it's at, keeping the point it was grabbed by
+        // under the pointer
+        for pointer in &self.pointers.down {
+            if let Some(offset) = pointer.light_offset {
+                let screen = self.grid.to_device(&pointer.current_position);
+                let on_plane = self.grid.unproject_at_z(screen, self.grid.light_source.z);
+                self.grid.light_source.x = on_plane.x + offset.x;
+                self.grid.light_source.y = on_plane.y + offset.y;
+            }
+        }
+
+        // Each held cube heads for wherever puts the point it was grabbed by right under
+        // its pointer. Rather than following how far the pointer moved each frame, which
+        // drifts, this catches back up as soon as whatever was in the way is gone.
+        //
+        // Going in order of cube id, each one is stopped by the ones already moved; held
+        // cubes don't push each other.
+        let mut dragging: Vec<(Grab, Coord)> = self
+            .pointers
+            .down
+            .iter()
+            .filter_map(|pointer| pointer.grab.map(|grab| (grab, pointer.current_position)))
+            .collect();
+        dragging.sort_by_key(|(grab, _)| grab.cube);
+
+        let mut movements: Vec<Movement> = vec![];
+        for (grab, position) in dragging {
+            let Some(cube) = self.cubes.iter_mut().find(|cube| cube.id == grab.cube) else {
+                continue;
+            };
+            cube.velocity = Coord { x: 0.0, y: 0.0 };
+
+            let screen = self.grid.to_device(&position);
+            let target = self.grid.unproject_at_z(screen, cube.z + grab.height);
+
+            let mut movement = Movement::new_from_delta(
+                *cube,
+                target.x - grab.offset.x - cube.coord.x,
+                target.y - grab.offset.y - cube.coord.y,
+            );
+
+            for other in &self.cubes {
+                if other.id == grab.cube || !held.contains(&other.id) {
+                    continue;
+                }
+
+                let other = movements
+                    .iter()
+                    .find(|movement| movement.end.id == other.id)
+                    .map_or(*other, |movement| movement.end);
+                movement = movement.clipped(&other);
+            }
+
+            if movement.dx != 0.0 || movement.dy != 0.0 {
+                movements.push(movement);
+            }
+        }
+
+        if !movements.is_empty() {
+            self.apply_movements(movements, frame);
+            self.layout_changed = true;
+        }
+
+        // Holding shift while grabbing a cube picks it up, to be dropped onto other cubes
+        self.lift_cubes(&self.pointers.lifted_cubes());
+
+        // Let go of whatever was being held, at the speed it was going. If it was lifted,
+        // gravity takes it from here. Cancelled pointers just drop their cube, and a
+        // click or tap on a control or a link uses it, unless it was part of a gesture.
+        for mut pointer in std::mem::take(&mut self.pointers.lifted) {
+            if let Some(Grab { cube: id, .. }) = pointer.grab {
+                let velocity = if pointer.is_cancelled {
+                    Coord { x: 0.0, y: 0.0 }
+                } else {
+                    pointer.velocity.velocity(pointer.last_event_time)
+                };
+
+                for cube in &mut self.cubes {
+                    if cube.id == id {
+                        cube.velocity = velocity;
+                    }
+                }
+            } else if pointer.light_offset.is_some() || pointer.is_gesture || pointer.is_cancelled {
+                continue;
+            } else if let Some(control) = Control::hit_test(&pointer.current_position, &self.grid) {
+                self.grid.rotate_by(control.quarter_turns());
+            } else {
+                for link in &self.links {
+                    if link.hit_test(&pointer.current_position, &self.grid) {
+                        frame.open_url = Some(link.url.clone());
+                    }
+                }
+            }
+        }
+
+        // Highlight whatever's being held, or else whatever the mouse is over
+        let hover = self.hover.filter(|_| self.pointers.down.is_empty());
+        self.active_control = hover.and_then(|hover| Control::hit_test(&hover, &self.grid));
+        let hover = hover.filter(|_| self.active_control.is_none());
+        let hovering_light = self.light_mode == LightMode::Fixed
+            && hover.is_some_and(|hover| light::handle_hit_test(&hover, &self.grid));
+        self.is_light_active = hovering_light || self.is_light_held();
+        let hover = hover.filter(|_| !hovering_light);
+        let hover_pick = hover.and_then(|hover| picking::pick(&self.cubes, &self.grid, &hover));
+
+        for cube in &mut self.cubes {
+            cube.is_active =
+                held.contains(&cube.id) || hover_pick.is_some_and(|pick| pick.id == cube.id);
+        }
+
+        let mut hovering_link = false;
+        for link in &mut self.links {
+            link.is_active = hover_pick.is_none()
+                && !hovering_link
+                && hover.is_some_and(|hover| link.hit_test(&hover, &self.grid));
+            hovering_link |= link.is_active;
+        }
+
+        self.cursor = if !held.is_empty() || hover_pick.is_some() || self.is_light_active {
+            Cursor::Grab
+        } else if hovering_link || self.active_control.is_some() {
+            Cursor::Link
+        } else {
+            Cursor::Default
+        };
+    }
+
+    // Moves the given cubes, pushing any others in their way through Movement::impact.
+    // Cubes which are moving on their own (being dragged or sliding) don't get pushed back.
+    fn apply_movements(&mut self, mut cubes_to_move: Vec<Movement>, frame: &mut Frame) {
+        let movers: Vec<usize> = cubes_to_move.iter().map(|m| m.end.id).collect();
+        let mut cubes_moved: HashSet<usize> = HashSet::new();
+
+        while let Some(movement) = cubes_to_move.pop() {
+            let mut last_movement: Option<Movement> = None;
+
+            for cube in &mut self.cubes {
+                if cube.id == movement.end.id {
+                    cube.translate(movement.dx, movement.dy);
+
+                    cubes_moved.insert(cube.id);
+
+                    last_movement = Some(movement);
+
+                    break;
+                }
+            }
+
+            if let Some(last_movement) = last_movement {
+                // check other overlapping cubes
+                for other_cube in &self.cubes {
+                    if !cubes_moved.contains(&other_cube.id) {
+                        if let Some(impact) = last_movement.impact(*other_cube) {
+                            cubes_to_move.push(impact);
+                        }
+                    }
+                }
+            }
+        }
+
+        // The pushes above only move each cube once, which can leave some overlapping
+        // when pushing into a cluster. Clean up whatever they missed.
+        if let Solution::GaveUp { overlapping } =
+            solver::solve(&mut self.cubes, &movers, solver::MAX_ITERATIONS)
+        {
+            frame.unresolved = overlapping;
+        }
+    }
+
+    // Slides any cubes which were let go while moving. Returns whether anything moved.
+    fn step_momentum(&mut self, dt: f64, frame: &mut Frame) -> bool {
+        let mut movements: Vec<Movement> = Vec::new();
+
+        for cube in &mut self.cubes {
+            if momentum::is_at_rest(cube.velocity) {
+                continue;
+            }
+
+            movements.push(Movement::new_from_delta(
+                *cube,
+                cube.velocity.x * dt,
+                cube.velocity.y * dt,
+            ));
+
+            cube.velocity = momentum::apply_friction(cube.velocity, self.friction, dt);
+        }
+
+        if movements.is_empty() {
+            false
+        } else {
+            self.apply_movements(movements, frame);
+            true
+        }
+    }
+
+    // Pulls every cube that's been moved back towards home. They go one at a time, so
+    // each one can push the others out of its way. Cubes which get boxed in hop over
+    // whatever's in the way, like they'd been lifted. Returns whether anything moved.
+    fn step_springs(&mut self, spring: Spring, dt: f64, frame: &mut Frame) -> bool {
+        let mut moved = false;
+
+        for i in 0..self.cubes.len() {
+            let mut cube = self.cubes[i];
+
+            if cube.coord == cube.home && momentum::is_at_rest(cube.velocity) {
+                self.homing.remove(&cube.id);
+                continue;
+            }
+
+            let mut homing = self.homing.get(&cube.id).copied().unwrap_or_default();
+
+            if homing.is_hopping {
+                cube.z = self.hop_height(&cube);
+                cube.velocity_z = 0.0;
+            }
+
+            let arrived = spring::has_arrived(cube.coord, cube.home, cube.velocity);
+            let (velocity, dx, dy) = if arrived {
+                let zero = Coord { x: 0.0, y: 0.0 };
+                (zero, cube.home.x - cube.coord.x, cube.home.y - cube.coord.y)
+            } else {
+                let velocity = spring.pull(cube.coord, cube.home, cube.velocity, dt);
+                (velocity, velocity.x * dt, velocity.y * dt)
+            };
+
+            cube.velocity = velocity;
+            self.cubes[i] = cube;
+            self.apply_movements(vec![Movement::new_from_delta(cube, dx, dy)], frame);
+
+            if arrived {
+                // If it was hopping, gravity takes it from here
+                self.homing.remove(&cube.id);
+            } else {
+                let distance = spring::distance(self.cubes[i].coord, cube.home);
+                homing.track(distance, dt);
+
+                if homing.is_stuck() && !self.homing.values().any(|other| other.is_hopping) {
+                    homing.is_hopping = true;
+                }
+                self.homing.insert(cube.id, homing);
+            }
+
+            moved = true;
+        }
+
+        if moved {
+            self.layout_changed = true;
+        }
+
+        moved
+    }
+
+    // High enough to pass over every other cube on the way home
+    fn hop_height(&self, cube: &Cube) -> f64 {
+        let passengers = gravity::passengers(cube, &self.cubes);
+
+        self.cubes
+            .iter()
+            .filter(|other| other.id != cube.id && !passengers.contains(&other.id))
+            .map(|other| other.top())
+            .fold(0.0, f64::max)
+            + gravity::LIFT_HEIGHT
+    }
+
+    // Carries lifted cubes just above whatever they're over
+    fn lift_cubes(&mut self, ids: &[usize]) {
+        let heights: Vec<(usize, f64)> = self
+            .cubes
+            .iter()
+            .filter(|cube| ids.contains(&cube.id))
+            .map(|cube| (cube.id, gravity::carry_height(cube, &self.cubes)))
+            .collect();
+
+        for (id, z) in heights {
+            for cube in &mut self.cubes {
+                if cube.id == id {
+                    cube.z = z;
+                    cube.velocity_z = 0.0;
+                }
+            }
+        }
+    }
+
+    // Lets the last scene's cubes fall, forgetting them once they're off screen.
+    // Returns whether any are still around.
+    fn step_leaving(&mut self, dt: f64) -> bool {
+        if self.leaving.is_empty() {
+            return false;
+        }
+
+        for cube in &mut self.leaving {
+            cube.velocity_z -= gravity::GRAVITY * dt;
+            cube.z += cube.velocity_z * dt;
+        }
+
+        // The back corner of the top face is the highest point of a cube on screen
+        let grid = &self.grid;
+        self.leaving
+            .retain(|cube| grid.project(cube.coord.x, cube.coord.y, cube.top()).y < grid.height);
+
+        true
+    }
+
+    // Drops any cubes left in mid-air. Returns whether any are still falling.
+    fn step_gravity(&mut self, dt: f64) -> bool {
+        let mut held = self.pointers.held_cubes();
+
+        // Cubes hopping home are carried, too
+        held.extend(
+            self.homing
+                .iter()
+                .filter(|(_, homing)| homing.is_hopping)
+                .map(|(id, _)| *id),
+        );
+
+        gravity::step(&mut self.cubes, &held, dt)
+    }
+}
+
+// The middle of where the scene put its cubes, for the view to turn around
+fn scene_center(cubes: &[Cube]) -> Coord {
+    let mut min = Coord {
+        x: f64::INFINITY,
+        y: f64::INFINITY,
+    };
+    let mut max = Coord {
+        x: f64::NEG_INFINITY,
+        y: f64::NEG_INFINITY,
+    };
+
+    for cube in cubes {
+        min.x = min.x.min(cube.home.x);
+        min.y = min.y.min(cube.home.y);
+        max.x = max.x.max(cube.home.x + cube.size);
+        max.y = max.y.max(cube.home.y + cube.size);
+    }
+
+    if cubes.is_empty() {
+        return Coord { x: 0.0, y: 0.0 };
+    }
+
+    Coord {
+        x: (min.x + max.x) / 2.0,
+        y: (min.y + max.y) / 2.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pointer::PointerKind;
+    use crate::render::{Command, DisplayList};
+    use geo::coord;
+
+    const FRAME: f64 = 16.0;
+
+    fn world(cubes: Vec<Cube>) -> World {
+        let mut world = World::new();
+        world.insert_cubes(cubes);
+        world.step(
+            &[Input::Resize {
+                width: 1000.0,
+                height: 800.0,
+                device_pixel_ratio: 1.0,
+            }],
+            0.0,
+        );
+        world
+    }
+
+    fn cube(world: &World, id: usize) -> Cube {
+        *world.cubes.iter().find(|cube| cube.id == id).unwrap()
+    }
+
+    // Where to put the pointer to grab the middle of a cube's top face
+    fn top_of(world: &World, id: usize) -> Coord {
+        let cube = cube(world, id);
+        let center = cube.bounds.center();
+        world.grid.project(center.x, center.y, cube.top())
+    }
+
+    fn pointer(id: i32, kind: PointerKind, is_pressed: bool, position: Coord, time: f64) -> Input {
+        Input::Pointer(PointerInput {
+            id,
+            kind,
+            is_pressed,
+            position,
+            time,
+            shift_key: false,
+        })
+    }
+
+    fn mouse(is_pressed: bool, position: Coord, time: f64) -> Input {
+        pointer(1, PointerKind::Mouse, is_pressed, position, time)
+    }
+
+    fn finger(id: i32, is_pressed: bool, position: Coord, time: f64) -> Input {
+        pointer(id, PointerKind::Touch, is_pressed, position, time)
+    }
+
+    // Screen offset which moves the pointer by (dx, dy) on the floor
+    fn iso_offset(position: Coord, dx: f64, dy: f64) -> Coord {
+        coord! { x: position.x + dx - dy, y: position.y + (dx + dy) / 2.0 }
+    }
+
+    fn run_until_still(world: &mut World) -> usize {
+        let mut frames = 0;
+        while world.step(&[], FRAME).redraw {
+            frames += 1;
+            assert!(frames < 1000);
+        }
+        frames
+    }
+
+    fn assert_no_overlaps(world: &World) {
+        assert_eq!(solver::overlapping_pairs(&world.cubes), vec![]);
+    }
+
+    #[test]
+    fn test_still_fits() {
+        // Every cube of every built-in page makes it into a link preview, whole
+        for scene in [
+            Scene::for_path("/").unwrap(),
+            Scene::for_path("/projects").unwrap(),
+            Scene::route("/nowhere").unwrap(),
+        ] {
+            let world = World::still(&scene, 1200.0, 630.0);
+            assert!(!world.cubes.is_empty());
+            for cube in &world.cubes {
+                let Corners { tl, tr, br, bl } = cube.corners();
+                for (corner, z) in [tl, tr, br, bl]
+                    .into_iter()
+                    .flat_map(|corner| [(corner, cube.z), (corner, cube.top())])
+                {
+                    let screen = world.grid.project(corner.x, corner.y, z);
+                    assert!(
+                        (0.0..=1200.0).contains(&screen.x) && (0.0..=630.0).contains(&screen.y),
+                        "{} at {:?}",
+                        cube.character(),
+                        screen
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_drag_pushes_cubes() {
+        let mut world = world(vec![
+            Cube::new('A', 0.0, 0.0, 100.0),
+            Cube::new('B', 120.0, 0.0, 100.0),
+            Cube::new('C', 230.0, 10.0, 60.0),
+        ]);
+
+        let start = top_of(&world, 1);
+        let frame = world.step(&[mouse(false, start, 0.0)], FRAME);
+        assert_eq!(frame.cursor, "grab");
+        world.step(&[mouse(true, start, 16.0)], FRAME);
+        assert!(cube(&world, 1).is_active);
+
+        // Drag 150 to the right, in a few big steps, slowly enough not to fling anything
+        for i in 1..=3 {
+            let position = iso_offset(start, 50.0 * i as f64, 0.0);
+            let frame = world.step(&[mouse(true, position, 1000.0 * i as f64)], FRAME);
+            assert_eq!(frame.cursor, "grabbing");
+            assert_no_overlaps(&world);
+        }
+
+        let end = iso_offset(start, 150.0, 0.0);
+        world.step(&[mouse(false, end, 5000.0)], FRAME);
+        run_until_still(&mut world);
+
+        assert_eq!(cube(&world, 1).coord.x, 150.0);
+        assert_eq!(cube(&world, 2).coord.x, 250.0);
+        assert_eq!(cube(&world, 3).coord.x, 350.0);
+        assert_no_overlaps(&world);
+    }
+
+    #[test]
+    fn test_fling() {
+        let mut world = world(vec![Cube::new('A', 0.0, 0.0, 100.0)]);
+
+        let start = top_of(&world, 1);
+        world.step(&[mouse(false, start, 0.0)], FRAME);
+        world.step(&[mouse(true, start, 16.0)], FRAME);
+        world.step(&[mouse(true, iso_offset(start, 20.0, 0.0), 32.0)], FRAME);
+        world.step(&[mouse(true, iso_offset(start, 40.0, 0.0), 48.0)], FRAME);
+        world.step(&[mouse(false, iso_offset(start, 40.0, 0.0), 50.0)], FRAME);
+
+        assert_eq!(cube(&world, 1).coord.x, 40.0);
+
+        // Keeps going after we let go, and eventually stops
+        let frames = run_until_still(&mut world);
+        assert!(frames > 10);
+        assert!(cube(&world, 1).coord.x > 100.0);
+        assert_eq!(cube(&world, 1).coord.y, 0.0);
+    }
+
+    #[test]
+    fn test_lift_and_stack() {
+        let mut world = world(vec![
+            Cube::new('A', 0.0, 0.0, 60.0),
+            Cube::new('B', 200.0, 0.0, 100.0),
+            Cube::new('C', 0.0, 200.0, 20.0),
+        ]);
+
+        let start = top_of(&world, 1);
+        let shift = |is_pressed, position, time| {
+            Input::Pointer(PointerInput {
+                id: 1,
+                kind: PointerKind::Mouse,
+                is_pressed,
+                position,
+                time,
+                shift_key: true,
+            })
+        };
+
+        world.step(&[shift(false, start, 0.0)], FRAME);
+        world.step(&[shift(true, start, 16.0)], FRAME);
+        assert_eq!(cube(&world, 1).z, gravity::LIFT_HEIGHT);
+
+        // Carried over C, which is short enough to clear instead of pushing
+        let over_c = iso_offset(start, -20.0, 180.0);
+        world.step(&[shift(true, over_c, 1000.0)], FRAME);
+        assert_eq!(cube(&world, 3).coord, coord! { x: 0.0, y: 200.0 });
+        assert_eq!(cube(&world, 1).z, 20.0 + gravity::LIFT_HEIGHT);
+
+        world.step(&[shift(false, over_c, 2000.0)], FRAME);
+        run_until_still(&mut world);
+
+        // It's held up off the floor, so to stay under the pointer it ends up further
+        // along than the pointer moved on the floor
+        assert_eq!(cube(&world, 1).z, 20.0);
+        assert_eq!(cube(&world, 1).coord, coord! { x: 10.0, y: 210.0 });
+
+        // B is too tall to carry anything over, so it gets pushed instead
+        let start = top_of(&world, 1);
+        world.step(&[shift(false, start, 3000.0)], FRAME);
+        world.step(&[shift(true, start, 3016.0)], FRAME);
+        world.step(
+            &[shift(true, iso_offset(start, 200.0, -180.0), 4000.0)],
+            FRAME,
+        );
+        assert!(cube(&world, 2).coord.x > 200.0);
+        assert_no_overlaps(&world);
+    }
+
+    // Loads a scene, pretending every link is 100 wide
+    fn load(world: &mut World, scene: &Scene, animate: bool) {
+        let links = scene
+            .links
+            .iter()
+            .map(|link| Link::new(link.x, link.y, link.text.clone(), link.url.clone(), 100.0))
+            .collect();
+        world.load_scene(scene, links, None, animate);
+    }
+
+    #[test]
+    fn test_scene_transition() {
+        let mut world = world(vec![]);
+        load(&mut world, &Scene::for_path("/").unwrap(), false);
+        assert_eq!(world.cubes.len(), 27);
+        assert!(world.cubes.iter().all(|cube| cube.z == 0.0));
+
+        let projects = Scene::for_path("/projects").unwrap();
+        load(&mut world, &projects, true);
+        assert_eq!(world.leaving.len(), 27);
+        assert_eq!(world.cubes.len(), 8);
+        assert_eq!(world.links[0].url, "https://github.com/artursapek/mondrian");
+        assert!(world.cubes.iter().all(|cube| cube.z >= DROP_HEIGHT));
+        assert!(world.step(&[], FRAME).redraw);
+
+        // The old cubes fall off screen and the new ones land where the scene put them
+        run_until_still(&mut world);
+        assert!(world.leaving.is_empty());
+        for spec in &projects.cubes {
+            let landed =
+                |cube: &Cube| cube.coord == coord! { x: spec.x, y: spec.y } && cube.z == 0.0;
+            assert!(world.cubes.iter().any(landed));
+        }
+    }
+
+    #[test]
+    fn test_saved_layout() {
+        let scene = Scene::for_path("/projects").unwrap();
+        let mut world = world(vec![]);
+        load(&mut world, &scene, false);
+        world.step(&[], FRAME);
+
+        // Drag a cube away from the rest
+        let start = top_of(&world, 1);
+        world.step(&[mouse(false, start, 0.0)], FRAME);
+        world.step(&[mouse(true, start, 16.0)], FRAME);
+        let frame = world.step(&[mouse(true, iso_offset(start, 300.0, 0.0), 1000.0)], FRAME);
+        assert!(!frame.layout_settled);
+
+        let frame = world.step(
+            &[mouse(false, iso_offset(start, 300.0, 0.0), 2000.0)],
+            FRAME,
+        );
+        assert!(frame.layout_settled);
+        assert!(!world.step(&[], FRAME).layout_settled);
+
+        let layout = SavedLayout::from_json(&world.saved_layout().to_json(), scene.hash).unwrap();
+
+        // It's still there after a reload
+        let mut reloaded = self::world(vec![]);
+        let links = vec![];
+        reloaded.load_scene(&scene, links, Some(&layout), false);
+        assert_eq!(cube(&reloaded, 1).coord.x, cube(&world, 1).home.x + 300.0);
+        assert_eq!(cube(&reloaded, 2).coord, cube(&world, 2).coord);
+
+        // Until the layout is reset, which drops it back home
+        reloaded.reset_layout();
+        assert!(cube(&reloaded, 1).z > 0.0);
+        assert_eq!(cube(&reloaded, 2).z, 0.0);
+        run_until_still(&mut reloaded);
+        assert_eq!(cube(&reloaded, 1).coord, cube(&reloaded, 1).home);
+        assert_eq!(cube(&reloaded, 1).z, 0.0);
+    }
+
+    #[test]
+    fn test_spring_home() {
+        let scene = Scene::parse(
+            "test.json",
+            r#"{
+                "text": [{ "text": "ARTUR", "x": 0, "y": 450, "size": 100 }],
+                "options": { "spring": { "idle_delay": 2 } }
+            }"#,
+        )
+        .unwrap();
+        let mut world = world(vec![]);
+        load(&mut world, &scene, false);
+
+        // Shove the first letter through the rest
+        let start = top_of(&world, 1);
+        world.step(&[mouse(false, start, 0.0)], FRAME);
+        world.step(&[mouse(true, start, 16.0)], FRAME);
+        for i in 1..=4 {
+            let position = iso_offset(start, 20.0 * i as f64, -100.0 * i as f64);
+            world.step(&[mouse(true, position, 1000.0 * i as f64)], FRAME);
+        }
+        let end = iso_offset(start, 80.0, -400.0);
+        world.step(&[mouse(false, end, 5000.0)], FRAME);
+        world.step(&[mouse(false, iso_offset(end, 0.0, 500.0), 5016.0)], FRAME);
+
+        assert!(!world.is_home());
+
+        // Nothing happens until it's been left alone for long enough
+        for _ in 0..100 {
+            world.step(&[], FRAME);
+        }
+        assert!(!world.is_home());
+
+        let mut frames = 0;
+        let mut frame = Frame::default();
+        while !world.is_home() {
+            frame = world.step(&[], FRAME);
+            assert_no_overlaps(&world);
+
+            frames += 1;
+            assert!(frames < 2000);
+        }
+
+        // Settled once they're all back, which clears the save rather than keeping one
+        // of them at home
+        assert!(frame.layout_settled);
+        assert!(!world.step(&[], FRAME).redraw);
+    }
+
+    #[test]
+    fn test_click_link() {
+        let mut world = world(vec![]);
+        let scene = Scene::parse(
+            "test.json",
+            r#"{ "links": [{ "x": 300, "y": 300, "text": "Blog", "url": "./blog" }] }"#,
+        )
+        .unwrap();
+        load(&mut world, &scene, false);
+
+        let position = world.grid.project(300.0, 280.0, 0.0);
+        let frame = world.step(&[mouse(false, position, 0.0)], FRAME);
+        assert_eq!(frame.cursor, "pointer");
+        assert_eq!(frame.open_url, None);
+
+        world.step(&[mouse(true, position, 16.0)], FRAME);
+        let frame = world.step(&[mouse(false, position, 32.0)], FRAME);
+        assert_eq!(frame.open_url, Some("./blog".to_string()));
+    }
+
+    #[test]
+    fn test_multi_touch() {
+        let mut world = world(vec![
+            Cube::new('A', 0.0, 0.0, 100.0),
+            Cube::new('B', 0.0, 300.0, 100.0),
+        ]);
+        let a = top_of(&world, 1);
+        let b = top_of(&world, 2);
+
+        // Both fingers drag their own cube at once
+        world.step(&[finger(7, true, a, 0.0), finger(9, true, b, 0.0)], FRAME);
+        let (a, b) = (iso_offset(a, 100.0, 0.0), iso_offset(b, 100.0, 0.0));
+        world.step(&[finger(7, true, a, 16.0), finger(9, true, b, 16.0)], FRAME);
+        assert_eq!(cube(&world, 1).coord, coord! { x: 100.0, y: 0.0 });
+        assert_eq!(cube(&world, 2).coord, coord! { x: 100.0, y: 300.0 });
+        assert!(world.is_dragging());
+
+        // Then push them into each other. Neither gives way; they stop touching.
+        let (a, b) = (iso_offset(a, 0.0, 150.0), iso_offset(b, 0.0, -150.0));
+        let frame = world.step(&[finger(7, true, a, 32.0), finger(9, true, b, 32.0)], FRAME);
+        assert_eq!(frame.unresolved, vec![]);
+        assert_no_overlaps(&world);
+        assert_eq!(cube(&world, 1).coord.y, 150.0);
+        assert_eq!(cube(&world, 2).coord.y, 250.0);
+
+        // Lifting one finger lets its cube go, at the speed the finger was going, but the
+        // other one's still held
+        let a = iso_offset(a, 50.0, 0.0);
+        world.step(
+            &[finger(7, true, a, 48.0), finger(9, false, b, 48.0)],
+            FRAME,
+        );
+        assert!(cube(&world, 2).velocity.y < 0.0);
+        assert!(!cube(&world, 2).is_active);
+        assert!(cube(&world, 1).is_active);
+        assert_eq!(cube(&world, 1).coord.x, 150.0);
+
+        world.step(&[finger(7, false, a, 64.0)], FRAME);
+        assert!(!world.is_dragging());
+        assert!(cube(&world, 1).velocity.x > 0.0);
+    }
+
+    #[test]
+    fn test_touch_gestures() {
+        let mut world = world(vec![]);
+        let scene = Scene::parse(
+            "test.json",
+            r#"{ "links": [{ "x": 300, "y": 300, "text": "Blog", "url": "./blog" }] }"#,
+        )
+        .unwrap();
+        load(&mut world, &scene, false);
+
+        let link = world.grid.project(300.0, 280.0, 0.0);
+        let elsewhere = world.grid.project(-300.0, -300.0, 0.0);
+
+        // Two fingers on empty space aren't a tap, even when one lifts off over a link
+        world.step(
+            &[finger(1, true, link, 0.0), finger(2, true, elsewhere, 0.0)],
+            FRAME,
+        );
+        let frame = world.step(&[finger(1, false, link, 16.0)], FRAME);
+        assert_eq!(frame.open_url, None);
+        let frame = world.step(&[finger(2, false, elsewhere, 32.0)], FRAME);
+        assert_eq!(frame.open_url, None);
+
+        // One finger is
+        world.step(&[finger(3, true, link, 48.0)], FRAME);
+        let frame = world.step(&[finger(3, false, link, 64.0)], FRAME);
+        assert_eq!(frame.open_url, Some("./blog".to_string()));
+    }
+
+    #[test]
+    fn test_camera() {
+        let mut world = world(vec![Cube::new('A', 0.0, 0.0, 100.0)]);
+        let floor = |world: &World, position: Coord| {
+            let (x, y) = world.grid.cartesian_to_iso(position.x, position.y);
+            coord! { x: x, y: y }
+        };
+
+        // Two fingers on empty space drag the view along with them...
+        let (a, b) = (coord! { x: 100.0, y: 700.0 }, coord! { x: 300.0, y: 700.0 });
+        world.step(&[finger(1, true, a, 0.0), finger(2, true, b, 0.0)], FRAME);
+        let under = floor(&world, coord! { x: 200.0, y: 700.0 });
+
+        let (a, b) = (coord! { x: 150.0, y: 720.0 }, coord! { x: 350.0, y: 720.0 });
+        let frame = world.step(&[finger(1, true, a, 16.0), finger(2, true, b, 16.0)], FRAME);
+        assert!(frame.redraw);
+        assert_eq!(world.grid.camera.pan, coord! { x: 50.0, y: 20.0 });
+        assert_eq!(floor(&world, coord! { x: 250.0, y: 720.0 }), under);
+
+        // ...and spreading them apart zooms in around the middle of them
+        let (a, b) = (coord! { x: 50.0, y: 720.0 }, coord! { x: 450.0, y: 720.0 });
+        world.step(&[finger(1, true, a, 32.0), finger(2, true, b, 32.0)], FRAME);
+        assert_eq!(world.grid.camera.zoom, 2.0);
+        assert_eq!(floor(&world, coord! { x: 250.0, y: 720.0 }), under);
+        assert_eq!(cube(&world, 1).coord, coord! { x: 0.0, y: 0.0 });
+
+        world.step(
+            &[finger(1, false, a, 48.0), finger(2, false, b, 48.0)],
+            FRAME,
+        );
+
+        // Cubes still get dragged with the point they were grabbed by under the pointer
+        let top = world.grid.project(50.0, 50.0, 100.0);
+        world.step(&[mouse(true, top, 64.0)], FRAME);
+        world.step(&[mouse(true, iso_offset(top, 200.0, 0.0), 80.0)], FRAME);
+        assert_eq!(cube(&world, 1).coord, coord! { x: 100.0, y: 0.0 });
+        world.step(&[mouse(false, iso_offset(top, 200.0, 0.0), 96.0)], FRAME);
+
+        let frame = world.step(&[Input::ResetCamera], FRAME);
+        assert!(frame.redraw);
+        assert_eq!(world.grid.camera.zoom, 1.0);
+
+        world.step(&[Input::Pan { dx: 10.0, dy: 0.0 }], FRAME);
+        world.step(
+            &[Input::Zoom {
+                factor: 0.5,
+                position: coord! { x: 0.0, y: 0.0 },
+            }],
+            FRAME,
+        );
+        assert_eq!(world.grid.camera.zoom, 0.5);
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut world = world(vec![
+            Cube::new('A', 0.0, 0.0, 100.0),
+            Cube::new('B', 200.0, 0.0, 100.0),
+        ]);
+        world.grid.camera.pivot = scene_center(&world.cubes);
+        let ids = |world: &World| world.cubes.iter().map(|cube| cube.id).collect::<Vec<_>>();
+        assert_eq!(ids(&world), vec![2, 1]);
+
+        // Clicking the button turns the view, a bit at a time
+        let button = coord! { x: 1000.0 - 36.0, y: 800.0 - 36.0 };
+        world.step(&[mouse(true, button, 0.0)], FRAME);
+        assert!(!world.is_dragging());
+        world.step(&[mouse(false, button, 16.0)], FRAME);
+        assert_eq!(world.grid.camera.target_rotation, 1.0);
+
+        let frame = world.step(&[], FRAME);
+        assert!(frame.redraw);
+        assert!(world.grid.camera.rotation > 0.0 && world.grid.camera.rotation < 1.0);
+        run_until_still(&mut world);
+        assert_eq!(world.grid.camera.rotation, 1.0);
+
+        // Another quarter turn from the keyboard, and B is behind A
+        world.step(&[Input::Rotate { quarter_turns: 1 }], FRAME);
+        run_until_still(&mut world);
+        assert_eq!(ids(&world), vec![1, 2]);
+
+        // Cubes can still be grabbed and dragged where the pointer goes
+        let top = world.grid.project(50.0, 50.0, 100.0);
+        world.step(&[mouse(true, top, 0.0)], FRAME);
+        assert!(world.is_dragging());
+        let target = world.grid.project(50.0, -50.0, 100.0);
+        world.step(&[mouse(true, target, 16.0)], FRAME);
+        assert_eq!(cube(&world, 1).coord, coord! { x: 0.0, y: -100.0 });
+    }
+
+    #[test]
+    fn test_move_light() {
+        let mut world = world(vec![Cube::new('A', 0.0, 0.0, 100.0)]);
+        let light = world.grid.light_source;
+        let sun = world.grid.project(light.x, light.y, light.z);
+        let sun = coord! { x: sun.x, y: sun.y };
+
+        // Hovering over the sun, then dragging it moves the light without moving its
+        // height, and leaves the cubes alone
+        world.step(&[mouse(false, sun, 0.0)], FRAME);
+        assert!(world.is_light_active);
+        world.step(&[mouse(true, sun, 16.0)], FRAME);
+        assert!(!world.is_dragging());
+        let moved = coord! { x: sun.x + 100.0, y: sun.y };
+        world.step(&[mouse(true, moved, 32.0)], FRAME);
+        world.step(&[mouse(false, moved, 48.0)], FRAME);
+        let dragged = world.grid.light_source;
+        assert_eq!(dragged.z, light.z);
+        assert!((dragged.x - light.x - 50.0).abs() < 1e-9);
+        assert!((dragged.y - light.y + 50.0).abs() < 1e-9);
+        assert_eq!(cube(&world, 1).coord, coord! { x: 0.0, y: 0.0 });
+
+        // The flashlight follows the mouse around the floor
+        world.step(&[Input::NextLightMode], FRAME);
+        assert_eq!(world.light_mode, LightMode::Flashlight);
+        let floor = world.grid.project(300.0, 200.0, 0.0);
+        world.step(&[mouse(false, floor, 64.0)], FRAME);
+        let light = world.grid.light_source;
+        assert!((light.x - 300.0).abs() < 1e-9 && (light.y - 200.0).abs() < 1e-9);
+
+        // And in Day mode it follows the clock
+        world.step(&[Input::NextLightMode], FRAME);
+        let frame = world.step(&[Input::Clock { hours: 9.0 }], FRAME);
+        assert!(frame.redraw);
+        assert_eq!(
+            world.grid.light_source,
+            light::day_position(9.0, world.grid.camera.pivot)
+        );
+        assert!(!world.step(&[Input::Clock { hours: 9.0 }], FRAME).redraw);
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut world = world(vec![
+            Cube::new('A', 0.0, 0.0, 100.0),
+            Cube::new('B', 200.0, 0.0, 100.0),
+        ]);
+        world.step(&[mouse(false, top_of(&world, 1), 0.0)], FRAME);
+
+        let list = DisplayList::new();
+        world.draw(&list);
+        let commands = list.commands();
+        assert_eq!(
+            commands[0],
+            Command::Clear {
+                width: 1000.0,
+                height: 800.0
+            }
+        );
+
+        // Back to front, then the buttons on top
+        let texts: Vec<String> = list.texts().into_iter().map(|(text, _)| text).collect();
+        assert_eq!(texts, vec!["A", "B", "↺", "↻"]);
+
+        // The cube under the mouse is outlined in blue, and the other isn't
+        let mut outlines = vec![];
+        let (mut style, mut strokes) = (String::new(), vec![]);
+        for command in commands {
+            match command {
+                Command::SetStrokeStyle(new_style) => style = new_style,
+                Command::Stroke => strokes.push(style.clone()),
+                Command::FillText { .. } => outlines.push(std::mem::take(&mut strokes)),
+                _ => {}
+            }
+        }
+        assert!(outlines[0].iter().all(|style| style == "#0000ff"));
+        assert!(!outlines[1].is_empty() && outlines[1].iter().all(|style| style != "#0000ff"));
+    }
+
+    #[test]
+    fn test_cancel_drag() {
+        let mut world = world(vec![
+            Cube::new('A', 0.0, 0.0, 100.0),
+            Cube::new('B', 0.0, 300.0, 100.0),
+        ]);
+        let a = top_of(&world, 1);
+        let b = top_of(&world, 2);
+
+        // A mouse and a finger at once, in the same step
+        world.step(&[mouse(true, a, 0.0), finger(5, true, b, 0.0)], FRAME);
+        let (a, b) = (iso_offset(a, 100.0, 0.0), iso_offset(b, 100.0, 0.0));
+        world.step(&[mouse(true, a, 16.0), finger(5, true, b, 16.0)], FRAME);
+        assert_eq!(cube(&world, 1).coord.x, 100.0);
+        assert_eq!(cube(&world, 2).coord.x, 100.0);
+
+        // The browser takes the finger for itself; its cube stops dead
+        world.step(&[Input::PointerCancel { id: 5 }], FRAME);
+        assert!(momentum::is_at_rest(cube(&world, 2).velocity));
+        assert!(!cube(&world, 2).is_active);
+        assert!(world.is_dragging());
+
+        // Switching windows mid-drag drops the other one
+        world.step(&[Input::Blur], FRAME);
+        assert!(!world.is_dragging());
+        assert!(momentum::is_at_rest(cube(&world, 1).velocity));
+
+        // Moving the mouse again without the button down doesn't pick it back up
+        let frame = world.step(&[mouse(false, iso_offset(a, 300.0, 0.0), 80.0)], FRAME);
+        assert_eq!(cube(&world, 1).coord.x, 100.0);
+        assert_eq!(frame.cursor, "default");
+    }
+
+    #[test]
+    fn test_pointer_leave() {
+        let mut world = world(vec![Cube::new('A', 0.0, 0.0, 100.0)]);
+
+        // Hovering outlines the cube, and leaving the canvas lets it go again
+        let frame = world.step(&[mouse(false, top_of(&world, 1), 0.0)], FRAME);
+        assert!(cube(&world, 1).is_active);
+        assert_ne!(frame.cursor, "default");
+
+        let frame = world.step(&[Input::PointerLeave], FRAME);
+        assert!(!cube(&world, 1).is_active);
+        assert_eq!(frame.cursor, "default");
+    }
+
+    #[test]
+    fn test_grab_offset() {
+        let mut world = world(vec![
+            Cube::new('A', 0.0, 0.0, 100.0),
+            Cube::new('B', 0.0, 300.0, 100.0),
+        ]);
+
+        // Grab A by its left face, near the bottom corner, while B is held still
+        let grid = &world.grid;
+        let (a, b) = (grid.project(80.0, 100.0, 30.0), top_of(&world, 2));
+        world.step(&[finger(1, true, a, 0.0), finger(2, true, b, 0.0)], FRAME);
+
+        // Shoving A into B gets it stuck, with the finger carrying on ahead
+        let a = world.grid.project(80.0, 400.0, 30.0);
+        world.step(&[finger(1, true, a, 16.0)], FRAME);
+        assert_eq!(cube(&world, 1).coord, coord! { x: 0.0, y: 200.0 });
+
+        // Once it's clear, it's straight back under the finger, by the same spot
+        let a = world.grid.project(300.0, 250.0, 30.0);
+        world.step(&[finger(1, true, a, 32.0)], FRAME);
+        assert_eq!(cube(&world, 1).coord, coord! { x: 220.0, y: 150.0 });
+
+        let pick = picking::pick(&world.cubes, &world.grid, &a).unwrap();
+        assert_eq!(pick.id, 1);
+        assert_eq!((pick.hit.x, pick.hit.y, pick.hit.z), (300.0, 250.0, 30.0));
+    }
+}
@@ -2,5 +2,9 @@ const { merge } = require('webpack-merge');
 const common = require('./webpack.common.js');
 
 module.exports = merge(common, {
-  mode: 'development'
+  mode: 'development',
+  devServer: {
+    // Every path is a scene, so serve the page for all of them
+    historyApiFallback: true
+  }
 });